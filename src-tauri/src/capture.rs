@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::Duration;
+
+/**
+Capture file layout (all integers little endian):
+
+  header: b"SDCAP" <version: u8>
+  record: <direction: u8> <monotonic µs: u64> <unix µs: u64> <len: u32> <data: [u8; len]>
+
+Records are appended as traffic happens, so a capture cut short by a crash
+simply ends at the last complete record.
+*/
+const MAGIC: &[u8; 5] = b"SDCAP";
+const VERSION: u8 = 1;
+
+pub const EXTENSION: &str = "sdcap";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum Direction {
+    Rx,
+    Tx,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Record {
+    pub direction: Direction,
+    /// Time since the start of the capture
+    pub monotonic: Duration,
+    /// Wall clock time, since the UNIX epoch
+    pub unix: Duration,
+    pub data: Vec<u8>,
+}
+
+pub struct CaptureWriter<W: Write> {
+    output: W,
+}
+
+impl CaptureWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        CaptureWriter::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> CaptureWriter<W> {
+    pub fn new(mut output: W) -> io::Result<Self> {
+        output.write_all(MAGIC)?;
+        output.write_all(&[VERSION])?;
+
+        Ok(CaptureWriter { output })
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        let direction = match record.direction {
+            Direction::Rx => 0u8,
+            Direction::Tx => 1u8,
+        };
+
+        self.output.write_all(&[direction])?;
+        self.output
+            .write_all(&(record.monotonic.as_micros() as u64).to_le_bytes())?;
+        self.output
+            .write_all(&(record.unix.as_micros() as u64).to_le_bytes())?;
+        self.output
+            .write_all(&(record.data.len() as u32).to_le_bytes())?;
+        self.output.write_all(&record.data)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+pub struct CaptureReader<R: Read> {
+    input: R,
+}
+
+impl CaptureReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        CaptureReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let mut header = [0u8; 6];
+        input.read_exact(&mut header)?;
+
+        if &header[..5] != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a capture file.",
+            ));
+        }
+
+        if header[5] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unsupported capture version {}.", header[5]),
+            ));
+        }
+
+        Ok(CaptureReader { input })
+    }
+
+    /// Returns `None` at the end of the capture, including a truncated final record
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        match self.read_record() {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            other => other.map(Some),
+        }
+    }
+
+    pub fn records(mut self) -> io::Result<Vec<Record>> {
+        let mut records = Vec::new();
+        while let Some(record) = self.next_record()? {
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+
+    fn read_record(&mut self) -> io::Result<Record> {
+        let mut head = [0u8; 21];
+        self.input.read_exact(&mut head)?;
+
+        let direction = match head[0] {
+            0 => Direction::Rx,
+            1 => Direction::Tx,
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid record direction {}.", other),
+                ));
+            }
+        };
+
+        let monotonic = u64::from_le_bytes(head[1..9].try_into().unwrap());
+        let unix = u64::from_le_bytes(head[9..17].try_into().unwrap());
+        let len = u32::from_le_bytes(head[17..21].try_into().unwrap());
+
+        let mut data = vec![0u8; len as usize];
+        self.input.read_exact(&mut data)?;
+
+        Ok(Record {
+            direction,
+            monotonic: Duration::from_micros(monotonic),
+            unix: Duration::from_micros(unix),
+            data,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(direction: Direction, ms: u64, data: &str) -> Record {
+        Record {
+            direction,
+            monotonic: Duration::from_millis(ms),
+            unix: Duration::from_millis(1_700_000_000_000 + ms),
+            data: data.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let records = vec![
+            record(Direction::Rx, 0, "motor_speed = 10\n"),
+            record(Direction::Tx, 5, "[ping]\n"),
+            record(Direction::Rx, 12, "[pong]\n"),
+        ];
+
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        for r in records.iter() {
+            writer.write(r).unwrap();
+        }
+
+        let reader = CaptureReader::new(writer.output.as_slice()).unwrap();
+        assert_eq!(reader.records().unwrap(), records);
+    }
+
+    #[test]
+    fn test_truncated_capture() {
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        writer.write(&record(Direction::Rx, 0, "complete\n")).unwrap();
+        writer.write(&record(Direction::Rx, 1, "cut off\n")).unwrap();
+
+        let bytes = &writer.output[..writer.output.len() - 3];
+        let records = CaptureReader::new(bytes).unwrap().records().unwrap();
+
+        assert_eq!(records, vec![record(Direction::Rx, 0, "complete\n")]);
+    }
+
+    #[test]
+    fn test_bad_header() {
+        assert!(CaptureReader::new(&b"NOTCAP"[..]).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::command::describe::{DeviceDescription, SET_SCHEMA};
+use crate::command::{Command, CommandHandler, HandlerContext};
+use crate::err::{Error, ErrorKind};
+use crate::project::DeviceEvent;
+
+/// Levels `[log <level> <message>]` accepts
+pub const LOG_LEVELS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+fn invalid<M: Into<String>>(message: M) -> Result<(), Error> {
+    Err(Error::new(ErrorKind::InvalidCommand, message))
+}
+
+///
+/// Captures `[set_schema <schema>]`, the device describing itself
+///
+pub struct SchemaHandler {}
+
+impl CommandHandler for SchemaHandler {
+    fn action(&self) -> String {
+        SET_SCHEMA.to_string()
+    }
+
+    fn handle(&self, command: &Command, context: &mut HandlerContext) -> Result<(), Error> {
+        let description = DeviceDescription::parse(command)?;
+
+        context.emit(DeviceEvent::Description(description));
+        context.consume();
+
+        Ok(())
+    }
+}
+
+///
+/// Answers `[ping ...]` with `[pong ...]`, echoing the arguments
+///
+pub struct PingHandler {}
+
+impl CommandHandler for PingHandler {
+    fn action(&self) -> String {
+        "ping".to_string()
+    }
+
+    fn handle(&self, command: &Command, context: &mut HandlerContext) -> Result<(), Error> {
+        context.reply(Command {
+            action: "pong".to_string(),
+            arguments: command.arguments.clone(),
+        });
+
+        Ok(())
+    }
+}
+
+///
+/// Checks `[log <level> <message>]`
+///
+pub struct LogHandler {}
+
+impl CommandHandler for LogHandler {
+    fn action(&self) -> String {
+        "log".to_string()
+    }
+
+    fn handle(&self, command: &Command, _: &mut HandlerContext) -> Result<(), Error> {
+        let Some(level) = command.arguments.first() else {
+            return invalid("`log` needs a level and a message");
+        };
+
+        if !LOG_LEVELS.contains(&level.to_ascii_lowercase().as_str()) {
+            return invalid(format!(
+                "`log` level must be one of {}, got {}",
+                LOG_LEVELS.join(", "),
+                level
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+///
+/// Checks `[number <id> <value>]`
+///
+pub struct NumberHandler {}
+
+impl CommandHandler for NumberHandler {
+    fn action(&self) -> String {
+        "number".to_string()
+    }
+
+    fn handle(&self, command: &Command, _: &mut HandlerContext) -> Result<(), Error> {
+        let [_, value] = command.arguments.as_slice() else {
+            return invalid("`number` takes an id and a value");
+        };
+
+        if value.parse::<f64>().is_err() {
+            return invalid(format!("`number` value {:?} is not a number", value));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogHandler, NumberHandler, PingHandler};
+    use crate::command::{Command, CommandHandler, HandlerContext};
+    use crate::timestamp::Timestamp;
+
+    fn command(action: &str, arguments: &[&str]) -> Command {
+        Command {
+            action: action.to_string(),
+            arguments: arguments.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_builtins() {
+        let mut context = HandlerContext::new(1, Timestamp::now());
+
+        PingHandler {}
+            .handle(&command("ping", &["7"]), &mut context)
+            .unwrap();
+        assert_eq!(context.replies()[0].encode(), "[pong 7]");
+        assert!(context.forward());
+
+        assert!(LogHandler {}.handle(&command("log", &["WARN", "hot"]), &mut context).is_ok());
+        assert!(LogHandler {}.handle(&command("log", &["loud", "hot"]), &mut context).is_err());
+        assert!(LogHandler {}.handle(&command("log", &[]), &mut context).is_err());
+
+        assert!(NumberHandler {}.handle(&command("number", &["rpm", "-1e3"]), &mut context).is_ok());
+        assert!(NumberHandler {}.handle(&command("number", &["rpm", "fast"]), &mut context).is_err());
+        assert!(NumberHandler {}.handle(&command("number", &["1"]), &mut context).is_err());
+    }
+}
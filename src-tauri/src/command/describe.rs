@@ -0,0 +1,195 @@
+use crate::command::reply::Replies;
+use crate::command::schema::CommandSchemas;
+use crate::command::Command;
+use crate::err::{Error, ErrorKind};
+use crate::project::{write_device, Projects};
+use crate::recording::Recorder;
+use crate::workspace::{CommandDefinition, CommandParameter};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+/// Action a device answers `[help --schema]` with
+pub const SET_SCHEMA: &str = "set_schema";
+
+pub fn help_schema() -> Command {
+    Command {
+        action: "help".to_string(),
+        arguments: vec!["--schema".to_string()],
+    }
+}
+
+#[derive(Deserialize)]
+struct SchemaParameter {
+    name: String,
+    #[serde(default = "default_type", rename = "type")]
+    value_type: String,
+    #[serde(default)]
+    min: Option<f64>,
+    #[serde(default)]
+    max: Option<f64>,
+    #[serde(default)]
+    options: Vec<String>,
+}
+
+fn default_type() -> String {
+    "string".to_string()
+}
+
+#[derive(Deserialize)]
+struct SchemaCommand {
+    name: String,
+    #[serde(default)]
+    display_name: Option<String>,
+    #[serde(default)]
+    parameters: Vec<SchemaParameter>,
+}
+
+///
+/// The JSON argument of `[set_schema <schema>]`, as firmware writes it:
+///
+/// `{"commands": [{"name": "motor", "parameters": [{"name": "Speed", "type": "number"}]}], "readouts": ["rpm"]}`
+///
+#[derive(Deserialize)]
+struct DeviceSchema {
+    #[serde(default)]
+    commands: Vec<SchemaCommand>,
+    #[serde(default)]
+    readouts: Vec<String>,
+}
+
+///
+/// What a device says about itself, ready to merge into a workspace
+///
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceDescription {
+    pub commands: Vec<CommandDefinition>,
+    pub readouts: Vec<String>,
+}
+
+impl DeviceDescription {
+    pub fn parse(command: &Command) -> Result<DeviceDescription, Error> {
+        let [schema] = command.arguments.as_slice() else {
+            return Err(Error::new(
+                ErrorKind::InvalidCommand,
+                format!("`{}` takes the schema as its only argument", SET_SCHEMA),
+            ));
+        };
+
+        let schema: DeviceSchema = serde_json::from_str(schema).map_err(|e| {
+            Error::new(
+                ErrorKind::InvalidCommand,
+                format!("The device sent an invalid schema: {}", e),
+            )
+        })?;
+
+        let commands = schema
+            .commands
+            .into_iter()
+            .enumerate()
+            .map(|(id, c)| CommandDefinition {
+                id: id as u64,
+                display_name: c.display_name.unwrap_or_else(|| c.name.clone()),
+                name: c.name,
+                icon: String::new(),
+                parameters: c
+                    .parameters
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, p)| CommandParameter {
+                        index: index as u64,
+                        display_name: p.name,
+                        value_type: p.value_type,
+                        min: p.min,
+                        max: p.max,
+                        options: p.options,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(DeviceDescription {
+            commands,
+            readouts: schema.readouts,
+        })
+    }
+}
+
+///
+/// Asks the device of a project to describe its commands and readouts
+/// with `[help --schema]` and waits for its `[set_schema ...]` reply.
+///
+#[tauri::command]
+pub async fn device_describe(
+    project_id: u64,
+    timeout: Option<u64>,
+    projects: State<'_, Projects>,
+    replies: State<'_, Arc<Replies>>,
+    schemas: State<'_, Arc<CommandSchemas>>,
+    recorder: State<'_, Arc<Recorder>>,
+) -> Result<DeviceDescription, Error> {
+    let device = projects
+        .lock()
+        .unwrap()
+        .get(&project_id)
+        .ok_or_else(|| Error::new(ErrorKind::NoSuchProject, "Cannot find this project."))?
+        .device()
+        .cloned()
+        .ok_or_else(|| Error::new(ErrorKind::NoSuchDevice, "This project has no device."))?;
+
+    let mut reply = replies.expect(device.id(), |c| c.action == SET_SCHEMA);
+    let request = help_schema().encode_with(&schemas.syntax(device.id()));
+
+    write_device(device.id(), format!("{}\n", request).as_bytes(), &recorder)?;
+
+    let command = reply
+        .wait(Duration::from_millis(timeout.unwrap_or(1000)))
+        .await
+        .ok_or_else(|| Error::new(ErrorKind::Timeout, "The device did not describe itself."))?;
+
+    DeviceDescription::parse(&command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{help_schema, DeviceDescription, SET_SCHEMA};
+    use crate::command::Command;
+
+    #[test]
+    fn test_parse_schema() {
+        assert_eq!(help_schema().encode(), "[help --schema]");
+
+        let command = Command {
+            action: SET_SCHEMA.to_string(),
+            arguments: vec![r#"{
+                "commands": [
+                    {"name": "ping"},
+                    {"name": "motor", "display_name": "Motor", "parameters": [
+                        {"name": "Speed", "type": "number", "max": 100},
+                        {"name": "Label"}
+                    ]}
+                ],
+                "readouts": ["rpm", "torque"]
+            }"#
+            .to_string()],
+        };
+
+        let description = DeviceDescription::parse(&command).unwrap();
+        assert_eq!(description.readouts, vec!["rpm", "torque"]);
+        assert_eq!(description.commands[0].display_name, "ping");
+
+        let motor = &description.commands[1];
+        assert_eq!(motor.id, 1);
+        assert_eq!(motor.parameters[0].max, Some(100.0));
+        assert_eq!(motor.parameters[1].index, 1);
+        assert_eq!(motor.parameters[1].value_type, "string");
+
+        let invalid = Command {
+            action: SET_SCHEMA.to_string(),
+            arguments: vec!["{".to_string()],
+        };
+        assert!(DeviceDescription::parse(&invalid).is_err());
+    }
+}
@@ -1,12 +1,24 @@
+pub mod builtin;
+pub mod describe;
+pub mod reply;
+pub mod request;
 mod route;
+pub mod schema;
 
 use std::fmt::Display;
-use crate::config::BuilderConfig;
+use crate::command::builtin::{LogHandler, NumberHandler, PingHandler, SchemaHandler};
+use crate::config::{BuilderConfig, Configuration};
+use crate::command::reply::Replies;
+use crate::command::schema::CommandSchemas;
 use crate::err::Error;
-use serde::Serialize;
+use crate::project::DeviceEvent;
+use crate::timestamp::Timestamp;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::io::{Cursor, Read};
-use std::sync::Mutex;
-use tauri::generate_handler;
+use std::sync::Arc;
+use tauri::{generate_handler, Builder, Runtime};
 
 /**
 Command examples:
@@ -24,79 +36,444 @@ impl<R: tauri::Runtime> BuilderConfig<R> {
         //     route::poll_commands,
         // ], &["poll_commands"])
         // .register_sink_factory(|| Box::new(CommandParser { buffer: vec![] }))
-        self
+        self.register_commands(
+            generate_handler![describe::device_describe, request::device_request],
+            &["device_describe", "device_request"],
+        )
+        .register_command_handler(Box::new(SchemaHandler {}))
+        .register_command_handler(Box::new(PingHandler {}))
+        .register_command_handler(Box::new(LogHandler {}))
+        .register_command_handler(Box::new(NumberHandler {}))
+        .fold(|b| {
+            b.manage(Arc::new(CommandSchemas::default()))
+                .manage(Arc::new(Replies::default()))
+        })
     }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Command {
     pub action: String,
     pub arguments: Vec<String>,
 }
 
+impl Command {
+    /// Formats the command the way `CommandParser::new` reads it
+    pub fn encode(&self) -> String {
+        self.encode_with(&CommandSyntax::default())
+    }
+
+    /// Formats the command the way a `CommandParser` with `syntax` reads it
+    pub fn encode_with(&self, syntax: &CommandSyntax) -> String {
+        let mut encoded = String::from(syntax.open);
+        encoded.push_str(&quote(&self.action, syntax));
+
+        for argument in &self.arguments {
+            encoded.push(syntax.separator);
+            encoded.push_str(&quote(argument, syntax));
+        }
+
+        encoded.push(syntax.close);
+        encoded
+    }
+}
+
+fn quote(token: &str, syntax: &CommandSyntax) -> String {
+    let plain = !token.is_empty()
+        && !token.chars().any(|c| {
+            c == syntax.open
+                || c == syntax.close
+                || c == syntax.separator
+                || c == '"'
+                || c == '\\'
+                || c.is_control()
+        });
+
+    if plain {
+        return token.to_string();
+    }
+
+    let mut quoted = String::from('"');
+    for c in token.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\0' => quoted.push_str("\\0"),
+            _ => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
 impl Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", format!("Command [{} -> {}]", self.action, self.arguments.join(", ")))
     }
 }
 
-pub trait CommandHandler {
+///
+/// Reacts to received commands with its action before they are forwarded to
+/// the frontend. An error is sent to the frontend as a parse error in place
+/// of the command.
+///
+pub trait CommandHandler: Send + Sync {
     fn action(&self) -> String;
 
-    fn handle(&self, command: &Command);
+    fn handle(&self, command: &Command, context: &mut HandlerContext) -> Result<(), Error>;
+}
+
+/// Handlers by the action they handle
+pub type CommandHandlers = HashMap<String, Vec<Arc<dyn CommandHandler>>>;
+
+///
+/// What handlers want done with a received command
+///
+pub struct HandlerContext {
+    pub device: u64,
+    pub time: Timestamp,
+    replies: Vec<Command>,
+    events: Vec<DeviceEvent>,
+    forward: bool,
+}
+
+impl HandlerContext {
+    pub fn new(device: u64, time: Timestamp) -> HandlerContext {
+        HandlerContext {
+            device,
+            time,
+            replies: Vec::new(),
+            events: Vec::new(),
+            forward: true,
+        }
+    }
+
+    /// Writes a command back to the device
+    pub fn reply(&mut self, command: Command) {
+        self.replies.push(command);
+    }
+
+    /// Sends an event to the frontend
+    pub fn emit(&mut self, event: DeviceEvent) {
+        self.events.push(event);
+    }
+
+    /// Keeps the command from the frontend
+    pub fn consume(&mut self) {
+        self.forward = false;
+    }
+
+    pub fn replies(&self) -> &[Command] {
+        &self.replies
+    }
+
+    pub fn forward(&self) -> bool {
+        self.forward
+    }
+
+    pub fn into_parts(self) -> (Vec<Command>, Vec<DeviceEvent>, bool) {
+        (self.replies, self.events, self.forward)
+    }
+}
+
+struct CommandHandlerConfig {
+    handlers: CommandHandlers,
+}
+
+impl<R: Runtime> Configuration<R> for CommandHandlerConfig {
+    fn configure(self: Box<Self>, builder: Builder<R>) -> Builder<R> {
+        builder.manage(self.handlers)
+    }
+}
+
+impl<R: Runtime> BuilderConfig<R> {
+    pub fn register_command_handler(self, handler: Box<dyn CommandHandler>) -> Self {
+        self.register_config("command_handler", || {
+            Box::new(CommandHandlerConfig {
+                handlers: Default::default(),
+            })
+        })
+        .get_config::<CommandHandlerConfig>("command_handler", |mut c| {
+            c.handlers
+                .entry(handler.action())
+                .or_default()
+                .push(Arc::from(handler));
+            c
+        })
+    }
+}
+
+///
+/// The bytes that delimit commands and their arguments, from the `commands`
+/// section of a device config
+///
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct CommandSyntax {
+    #[serde(default = "default_open")]
+    pub open: char,
+    #[serde(default = "default_close")]
+    pub close: char,
+    #[serde(default = "default_separator")]
+    pub separator: char,
+    /// Longest command, from its opening to its closing delimiter
+    #[serde(default = "default_max_length", deserialize_with = "deserialize_max_length")]
+    pub max_length: usize,
+}
+
+fn default_open() -> char {
+    '['
+}
+
+fn default_close() -> char {
+    ']'
+}
+
+fn default_separator() -> char {
+    ' '
+}
+
+fn default_max_length() -> usize {
+    4096
+}
+
+/// Anything shorter cuts off all but the most trivial commands
+const MIN_MAX_LENGTH: usize = 16;
+
+fn deserialize_max_length<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let max_length = usize::deserialize(deserializer)?;
+
+    if max_length < MIN_MAX_LENGTH {
+        return Err(D::Error::custom(format!(
+            "max_length must be at least {}, got {}",
+            MIN_MAX_LENGTH, max_length
+        )));
+    }
+
+    Ok(max_length)
+}
+
+impl Default for CommandSyntax {
+    fn default() -> Self {
+        CommandSyntax {
+            open: default_open(),
+            close: default_close(),
+            separator: default_separator(),
+            max_length: default_max_length(),
+        }
+    }
+}
+
+const QUOTE: u8 = b'"';
+const ESCAPE: u8 = b'\\';
+
+///
+/// A command that could not be parsed, with whatever was received of it
+///
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub content: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum State {
+    /// Between commands, waiting for an opening delimiter
+    Outside,
+    Inside,
+    Quoted,
+    Escaped { quoted: bool },
 }
 
+///
+/// Incremental tokenizer for `[action arg "quoted arg" escaped\ arg]`.
+/// Received bytes can be split anywhere, `extend` carries the state over and
+/// queues every command (or error) it completes for `parse`.
+///
 pub struct CommandParser {
-    buffer: Vec<u8>,
+    open: u8,
+    close: u8,
+    separator: u8,
+    max_length: usize,
+    state: State,
+    /// Everything received of the current command, for errors
+    raw: Vec<u8>,
+    tokens: Vec<Vec<u8>>,
+    token: Option<Vec<u8>>,
+    parsed: VecDeque<Result<Command, ParseError>>,
 }
-//
-// impl DataSink for CommandParser {
-//     fn sink(&mut self, buf: &[u8]) -> Result<(), Error> {
-//         self.buffer.extend_from_slice(buf);
-//
-//         Ok(())
-//     }
-// }
 
 impl CommandParser {
     pub fn new() -> CommandParser {
-        CommandParser { buffer: Vec::new() }
+        Self::with_syntax(CommandSyntax::default()).unwrap()
+    }
+
+    pub fn with_syntax(syntax: CommandSyntax) -> Result<CommandParser, Error> {
+        let byte = |field: &str, c: char| {
+            if !c.is_ascii() || c as u8 == QUOTE || c as u8 == ESCAPE {
+                Err(Error::invalid_config(
+                    field,
+                    format!("{:?} cannot delimit commands", c),
+                ))
+            } else {
+                Ok(c as u8)
+            }
+        };
+
+        let open = byte("commands.open", syntax.open)?;
+        let close = byte("commands.close", syntax.close)?;
+        let separator = byte("commands.separator", syntax.separator)?;
+
+        if open == close || open == separator || close == separator {
+            return Err(Error::invalid_config(
+                "commands",
+                "open, close and separator must differ",
+            ));
+        }
+
+        Ok(CommandParser {
+            open,
+            close,
+            separator,
+            max_length: syntax.max_length,
+            state: State::Outside,
+            raw: Vec::new(),
+            tokens: Vec::new(),
+            token: None,
+            parsed: VecDeque::new(),
+        })
     }
 
     pub fn extend(&mut self, buf: &[u8]) -> Result<(), Error> {
-        self.buffer.extend_from_slice(buf);
+        for b in buf {
+            self.push(*b);
+        }
 
         Ok(())
     }
 
-    pub fn parse(&mut self) -> Option<Command> {
-        let start = if let Some(start) = self.buffer.iter().position(|b| *b == b'[') {
-            start
-        } else {
-            self.buffer.clear();
+    /// Next command completed by the data so far
+    pub fn parse(&mut self) -> Option<Result<Command, ParseError>> {
+        self.parsed.pop_front()
+    }
+
+    fn push(&mut self, b: u8) {
+        if self.state == State::Outside {
+            if b == self.open {
+                self.begin();
+            }
 
-            return None;
-        };
+            return;
+        }
 
-        self.buffer.drain(..=start); // Dont care about these bytes
-        let end = self.buffer.iter().position(|b| *b == b']')?;
+        self.raw.push(b);
 
-        let str = String::from_utf8(self.buffer[..end].to_vec()).ok()?;
+        match self.state {
+            State::Outside => unreachable!(),
+            State::Inside => {
+                if b == self.close {
+                    self.finish();
+                    return;
+                } else if b == self.open {
+                    // Lost the end of the previous command, this starts a new one
+                    self.raw.pop();
+                    self.fail("unterminated command");
+                    self.begin();
+                    return;
+                } else if b == self.separator {
+                    if let Some(token) = self.token.take() {
+                        self.tokens.push(token);
+                    }
+                } else if b == QUOTE {
+                    self.token.get_or_insert_default();
+                    self.state = State::Quoted;
+                } else if b == ESCAPE {
+                    self.token.get_or_insert_default();
+                    self.state = State::Escaped { quoted: false };
+                } else {
+                    self.token.get_or_insert_default().push(b);
+                }
+            }
+            State::Quoted => match b {
+                QUOTE => self.state = State::Inside,
+                ESCAPE => self.state = State::Escaped { quoted: true },
+                _ => self.token.get_or_insert_default().push(b),
+            },
+            State::Escaped { quoted } => {
+                let unescaped = match b {
+                    b'n' => b'\n',
+                    b'r' => b'\r',
+                    b't' => b'\t',
+                    b'0' => 0,
+                    _ => b,
+                };
 
-        let parts = str.split(' ').into_iter().collect::<Vec<&str>>();
+                self.token.get_or_insert_default().push(unescaped);
+                self.state = if quoted { State::Quoted } else { State::Inside };
+            }
+        }
 
-        let action = parts.first()?.to_string();
+        // The opening delimiter is not in raw
+        if self.raw.len() + 1 >= self.max_length && self.state != State::Outside {
+            self.fail(&format!("command is longer than {} bytes", self.max_length));
+        }
+    }
 
-        let command = Command {
-            action,
-            arguments: if (parts.len() == 1) {
-                vec![]
-            } else {
-                parts[1..].iter().map(|s| s.to_string()).collect()
-            },
+    fn begin(&mut self) {
+        self.state = State::Inside;
+        self.raw.clear();
+        self.tokens.clear();
+        self.token = None;
+    }
+
+    fn fail(&mut self, message: &str) {
+        let mut content = vec![self.open];
+        content.append(&mut self.raw);
+
+        self.parsed.push_back(Err(ParseError {
+            message: message.to_string(),
+            content: String::from_utf8_lossy(&content).to_string(),
+        }));
+
+        self.state = State::Outside;
+        self.tokens.clear();
+        self.token = None;
+    }
+
+    fn finish(&mut self) {
+        if let Some(token) = self.token.take() {
+            self.tokens.push(token);
+        }
+
+        let tokens = std::mem::take(&mut self.tokens)
+            .into_iter()
+            .map(String::from_utf8)
+            .collect::<Result<Vec<String>, _>>();
+
+        let mut tokens = match tokens {
+            Ok(tokens) => tokens.into_iter(),
+            Err(_) => return self.fail("command is not valid UTF-8"),
         };
 
-        Some(command)
+        let Some(action) = tokens.next() else {
+            return self.fail("command has no action");
+        };
+
+        self.parsed.push_back(Ok(Command {
+            action,
+            arguments: tokens.collect(),
+        }));
+
+        self.state = State::Outside;
+        self.raw.clear();
     }
 }
 
@@ -110,8 +487,119 @@ mod tests {
 
         let text = "asdf [hey do this!] slakdjf";
         parser.extend(text.as_bytes()).unwrap();
-        let command = parser.parse().unwrap();
+        let command = parser.parse().unwrap().unwrap();
         assert_eq!(command.action, "hey");
         assert_eq!(command.arguments, vec!["do", "this!"]);
+        assert!(parser.parse().is_none());
+    }
+
+    fn commands(parser: &mut CommandParser) -> Vec<Result<(String, Vec<String>), String>> {
+        std::iter::from_fn(|| parser.parse())
+            .map(|r| r.map(|c| (c.action, c.arguments)).map_err(|e| e.content))
+            .collect()
+    }
+
+    #[test]
+    fn test_quotes_and_escapes() {
+        let mut parser = CommandParser::new();
+
+        parser
+            .extend(br#"[log  info "motor ]hot\"" a\ b c\]d]"#)
+            .unwrap();
+
+        assert_eq!(
+            commands(&mut parser),
+            vec![Ok((
+                "log".to_string(),
+                vec!["info", "motor ]hot\"", "a b", "c]d"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect()
+            ))]
+        );
+    }
+
+    #[test]
+    fn test_split_across_reads() {
+        let mut parser = CommandParser::new();
+
+        parser.extend(b"speed = 1 [num").unwrap();
+        assert!(parser.parse().is_none());
+        parser.extend(b"ber rpm \"12").unwrap();
+        assert!(parser.parse().is_none());
+        parser.extend(b"0\"] [ping]").unwrap();
+
+        let parsed = commands(&mut parser);
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0], Ok(("number".to_string(), vec!["rpm".to_string(), "120".to_string()])));
+        assert_eq!(parsed[1], Ok(("ping".to_string(), vec![])));
+    }
+
+    #[test]
+    fn test_recovery() {
+        let mut parser = CommandParser::with_syntax(CommandSyntax {
+            open: '<',
+            close: '>',
+            separator: ',',
+            max_length: 16,
+        })
+        .unwrap();
+
+        parser.extend(b"<a,b <> <0123456789abcdefgh <set,x,1>").unwrap();
+
+        assert_eq!(
+            commands(&mut parser),
+            vec![
+                Err("<a,b ".to_string()),
+                Err("<>".to_string()),
+                Err("<0123456789abcde".to_string()),
+                Ok(("set".to_string(), vec!["x".to_string(), "1".to_string()])),
+            ]
+        );
+
+        assert!(CommandParser::with_syntax(CommandSyntax {
+            open: '"',
+            ..CommandSyntax::default()
+        })
+        .is_err());
+
+        assert!(serde_json::from_str::<CommandSyntax>(r#"{"max_length": 2}"#).is_err());
+        assert_eq!(
+            serde_json::from_str::<CommandSyntax>(r#"{"max_length": 16}"#).unwrap().max_length,
+            16
+        );
+    }
+
+    #[test]
+    fn test_encode() {
+        let command = Command {
+            action: "log".to_string(),
+            arguments: vec!["info".to_string(), "say \"hi\"]".to_string(), "".to_string()],
+        };
+
+        let encoded = command.encode();
+        assert_eq!(encoded, r#"[log info "say \"hi\"]" ""]"#);
+
+        let mut parser = CommandParser::new();
+        parser.extend(encoded.as_bytes()).unwrap();
+        assert_eq!(parser.parse().unwrap().unwrap().arguments, command.arguments);
+
+        let syntax = CommandSyntax {
+            open: '<',
+            close: '>',
+            separator: ',',
+            ..CommandSyntax::default()
+        };
+        let command = Command {
+            action: "set".to_string(),
+            arguments: vec!["a b".to_string(), "x,y>".to_string()],
+        };
+
+        let encoded = command.encode_with(&syntax);
+        assert_eq!(encoded, r#"<set,a b,"x,y>">"#);
+
+        let mut parser = CommandParser::with_syntax(syntax).unwrap();
+        parser.extend(encoded.as_bytes()).unwrap();
+        assert_eq!(parser.parse().unwrap().unwrap().arguments, command.arguments);
     }
 }
@@ -0,0 +1,112 @@
+use crate::command::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+///
+/// A command being waited for. Dropping it gives up.
+///
+pub struct Reply {
+    receiver: oneshot::Receiver<Command>,
+}
+
+impl Reply {
+    /// Waits without blocking a thread, a later wait continues where this one
+    /// timed out
+    pub async fn wait(&mut self, timeout: Duration) -> Option<Command> {
+        tokio::time::timeout(timeout, &mut self.receiver)
+            .await
+            .ok()?
+            .ok()
+    }
+}
+
+struct Waiter {
+    device: u64,
+    matcher: Box<dyn Fn(&Command) -> bool + Send>,
+    sender: oneshot::Sender<Command>,
+}
+
+///
+/// Commands somebody is waiting for, e.g. the reply to a request. Readers
+/// offer every command they parse, it goes to the oldest matching waiter.
+///
+#[derive(Default)]
+pub struct Replies {
+    waiting: Mutex<Vec<Waiter>>,
+}
+
+impl Replies {
+    ///
+    /// Waits for the next command from a device that matches. Expect before
+    /// writing the request, or a fast reply can be missed.
+    ///
+    pub fn expect<F>(&self, device: u64, matcher: F) -> Reply
+    where
+        F: Fn(&Command) -> bool + Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+
+        self.waiting.lock().unwrap().push(Waiter {
+            device,
+            matcher: Box::new(matcher),
+            sender,
+        });
+
+        Reply { receiver }
+    }
+
+    /// Returns true if somebody was waiting for this command
+    pub fn offer(&self, device: u64, command: &Command) -> bool {
+        let mut waiting = self.waiting.lock().unwrap();
+
+        // Forget whoever gave up
+        waiting.retain(|w| !w.sender.is_closed());
+
+        let Some(i) = waiting
+            .iter()
+            .position(|w| w.device == device && (w.matcher)(command))
+        else {
+            return false;
+        };
+
+        waiting.remove(i).sender.send(command.clone()).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Replies;
+    use crate::command::Command;
+    use std::time::Duration;
+    use tauri::async_runtime::block_on;
+
+    fn command(action: &str) -> Command {
+        Command {
+            action: action.to_string(),
+            arguments: vec![],
+        }
+    }
+
+    #[test]
+    fn test_offer() {
+        let replies = Replies::default();
+
+        let mut first = replies.expect(1, |c| c.action == "pong");
+        let mut second = replies.expect(1, |c| c.action == "pong");
+        drop(replies.expect(1, |_| true));
+
+        assert!(!replies.offer(2, &command("pong")));
+        assert!(!replies.offer(1, &command("other")));
+        assert!(replies.offer(1, &command("pong")));
+
+        block_on(async {
+            assert_eq!(first.wait(Duration::ZERO).await.unwrap().action, "pong");
+            assert!(second.wait(Duration::from_millis(10)).await.is_none());
+
+            // Still waiting after a timeout
+            assert!(replies.offer(1, &command("pong")));
+            assert_eq!(second.wait(Duration::ZERO).await.unwrap().action, "pong");
+        });
+    }
+}
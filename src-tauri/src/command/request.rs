@@ -0,0 +1,206 @@
+use crate::command::reply::Replies;
+use crate::command::schema::CommandSchemas;
+use crate::command::{Command, CommandSyntax};
+use crate::err::{Error, ErrorKind};
+use crate::project::{write_device, Projects};
+use crate::recording::Recorder;
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::State;
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+///
+/// How the reply to a request is recognised
+///
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReplyMatch {
+    /// The next command with this action, by default the request's own
+    Action {
+        #[serde(default)]
+        action: Option<String>,
+    },
+    /// The request carries a sequence number as its first argument, the
+    /// reply must carry the same one as its first argument
+    Sequence {
+        #[serde(default)]
+        action: Option<String>,
+    },
+    /// The reply, as the device writes it (e.g. `[action arguments...]`),
+    /// matches a regular expression
+    Pattern { pattern: String },
+}
+
+impl Default for ReplyMatch {
+    fn default() -> Self {
+        ReplyMatch::Action { action: None }
+    }
+}
+
+type Matcher = Box<dyn Fn(&Command) -> bool + Send>;
+
+impl ReplyMatch {
+    ///
+    /// Numbers the request if the reply is matched by sequence, and returns
+    /// what recognises its reply in the device's command syntax
+    ///
+    pub fn prepare(&self, request: &mut Command, syntax: &CommandSyntax) -> Result<Matcher, Error> {
+        Ok(match self {
+            ReplyMatch::Action { action } => {
+                let action = action.clone().unwrap_or_else(|| request.action.clone());
+
+                Box::new(move |c: &Command| c.action == action)
+            }
+            ReplyMatch::Sequence { action } => {
+                let sequence = SEQUENCE.fetch_add(1, Ordering::Relaxed).to_string();
+                request.arguments.insert(0, sequence.clone());
+
+                let action = action.clone();
+
+                Box::new(move |c: &Command| {
+                    c.arguments.first() == Some(&sequence)
+                        && action.as_ref().is_none_or(|a| a == &c.action)
+                })
+            }
+            ReplyMatch::Pattern { pattern } => {
+                let pattern = Regex::new(pattern).map_err(|e| {
+                    Error::new(
+                        ErrorKind::InvalidCommand,
+                        format!("Invalid reply pattern: {}", e),
+                    )
+                })?;
+
+                let syntax = syntax.clone();
+
+                Box::new(move |c: &Command| pattern.is_match(&c.encode_with(&syntax)))
+            }
+        })
+    }
+}
+
+///
+/// Sends a command to the device of a project and waits for its reply,
+/// resending it up to `retries` times. Replies are handed out oldest request
+/// first, so several requests can be outstanding on one device, and waiting
+/// for them does not hold up a runtime worker.
+///
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn device_request(
+    project_id: u64,
+    command: Command,
+    reply: Option<ReplyMatch>,
+    timeout: Option<u64>,
+    retries: Option<u32>,
+    projects: State<'_, Projects>,
+    replies: State<'_, Arc<Replies>>,
+    schemas: State<'_, Arc<CommandSchemas>>,
+    recorder: State<'_, Arc<Recorder>>,
+) -> Result<Command, Error> {
+    let device = projects
+        .lock()
+        .unwrap()
+        .get(&project_id)
+        .ok_or_else(|| Error::new(ErrorKind::NoSuchProject, "Cannot find this project."))?
+        .device()
+        .cloned()
+        .ok_or_else(|| Error::new(ErrorKind::NoSuchDevice, "This project has no device."))?;
+
+    // Before it is numbered, the schema does not know about that
+    if let Some(schema) = schemas.get(device.id())
+        && let Some(Err(e)) = schema.validate(&command)
+    {
+        return Err(e);
+    }
+
+    let syntax = schemas.syntax(device.id());
+    let mut request = command;
+    let matcher = reply.unwrap_or_default().prepare(&mut request, &syntax)?;
+
+    let mut waiting = replies.expect(device.id(), matcher);
+    let encoded = format!("{}\n", request.encode_with(&syntax));
+    let timeout = Duration::from_millis(timeout.unwrap_or(1000));
+    let attempts = retries.unwrap_or(0) + 1;
+
+    for _ in 0..attempts {
+        write_device(device.id(), encoded.as_bytes(), &recorder)?;
+
+        if let Some(reply) = waiting.wait(timeout).await {
+            return Ok(reply);
+        }
+    }
+
+    Err(Error::new(
+        ErrorKind::Timeout,
+        format!(
+            "No reply to `{}` after {} attempts.",
+            request.action, attempts
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReplyMatch;
+    use crate::command::{Command, CommandSyntax};
+    use serde_json::json;
+
+    fn command(action: &str, arguments: &[&str]) -> Command {
+        Command {
+            action: action.to_string(),
+            arguments: arguments.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_match_replies() {
+        let syntax = CommandSyntax::default();
+
+        let mut request = command("set_torque_limit", &["120"]);
+        let matcher = ReplyMatch::default().prepare(&mut request, &syntax).unwrap();
+        assert!(matcher(&command("set_torque_limit", &["ok"])));
+        assert!(!matcher(&command("log", &["info", "hi"])));
+
+        let sequence: ReplyMatch =
+            serde_json::from_value(json!({"type": "sequence", "action": "ack"})).unwrap();
+        let mut first = command("set_torque_limit", &["120"]);
+        let mut second = command("set_torque_limit", &["80"]);
+        let first_matcher = sequence.prepare(&mut first, &syntax).unwrap();
+        let second_matcher = sequence.prepare(&mut second, &syntax).unwrap();
+
+        assert_ne!(first.arguments[0], second.arguments[0]);
+        assert_eq!(first.arguments[1], "120");
+        assert!(first_matcher(&command("ack", &[&first.arguments[0]])));
+        assert!(!first_matcher(&command("ack", &[&second.arguments[0]])));
+        assert!(!first_matcher(&command("nak", &[&first.arguments[0]])));
+        assert!(second_matcher(&command("ack", &[&second.arguments[0]])));
+
+        let pattern = ReplyMatch::Pattern {
+            pattern: r"^\[limit (ok|err)".to_string(),
+        };
+        let matcher = pattern.prepare(&mut request, &syntax).unwrap();
+        assert!(matcher(&command("limit", &["ok", "120"])));
+        assert!(!matcher(&command("limit", &["maybe"])));
+
+        let angled = CommandSyntax {
+            open: '<',
+            close: '>',
+            separator: ',',
+            ..CommandSyntax::default()
+        };
+        let pattern = ReplyMatch::Pattern {
+            pattern: r"^<limit,ok>$".to_string(),
+        };
+        let matcher = pattern.prepare(&mut request, &angled).unwrap();
+        assert!(matcher(&command("limit", &["ok"])));
+
+        let invalid = ReplyMatch::Pattern {
+            pattern: "(".to_string(),
+        };
+        assert!(invalid.prepare(&mut request, &syntax).is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use crate::command::{Command, CommandSyntax};
+use crate::err::{Error, ErrorKind};
+use crate::workspace::{CommandDefinition, CommandParameter};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+///
+/// An argument coerced to the type its parameter declares
+///
+#[derive(Serialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum TypedValue {
+    String(String),
+    Integer(i64),
+    Number(f64),
+    Boolean(bool),
+    Bytes(Vec<u8>),
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct TypedCommand {
+    pub action: String,
+    pub arguments: Vec<TypedValue>,
+}
+
+///
+/// The commands a workspace defines in its command panels, by action
+///
+#[derive(Default, Debug)]
+pub struct CommandSchema {
+    commands: HashMap<String, Vec<CommandParameter>>,
+}
+
+impl CommandSchema {
+    pub fn new<'a, I: IntoIterator<Item = &'a CommandDefinition>>(definitions: I) -> CommandSchema {
+        let commands = definitions
+            .into_iter()
+            .map(|d| {
+                let mut parameters = d.parameters.clone();
+                parameters.sort_by_key(|p| p.index);
+
+                (d.name.clone(), parameters)
+            })
+            .collect();
+
+        CommandSchema { commands }
+    }
+
+    pub fn defines(&self, action: &str) -> bool {
+        self.commands.contains_key(action)
+    }
+
+    ///
+    /// Checks a command against its definition and coerces its arguments.
+    /// Returns `None` for actions the schema does not define.
+    ///
+    pub fn validate(&self, command: &Command) -> Option<Result<TypedCommand, Error>> {
+        let parameters = self.commands.get(&command.action)?;
+
+        if parameters.len() != command.arguments.len() {
+            return Some(Err(Error::new(
+                ErrorKind::InvalidCommand,
+                format!(
+                    "`{}` takes {} arguments, got {}",
+                    command.action,
+                    parameters.len(),
+                    command.arguments.len()
+                ),
+            )));
+        }
+
+        let arguments = parameters
+            .iter()
+            .zip(command.arguments.iter())
+            .map(|(parameter, argument)| {
+                coerce(parameter, argument).map_err(|expected| {
+                    Error::new(
+                        ErrorKind::InvalidCommand,
+                        format!(
+                            "`{}` argument {}: expected {}, got {:?}",
+                            command.action, parameter.display_name, expected, argument
+                        ),
+                    )
+                })
+            })
+            .collect::<Result<Vec<TypedValue>, Error>>();
+
+        Some(arguments.map(|arguments| TypedCommand {
+            action: command.action.clone(),
+            arguments,
+        }))
+    }
+}
+
+fn in_range(parameter: &CommandParameter, value: f64) -> Result<(), String> {
+    let below = parameter.min.is_some_and(|min| value < min);
+    let above = parameter.max.is_some_and(|max| value > max);
+
+    if below || above {
+        Err(format!(
+            "a value in [{}, {}]",
+            parameter.min.map_or("..".to_string(), |m| m.to_string()),
+            parameter.max.map_or("..".to_string(), |m| m.to_string())
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_integer(argument: &str) -> Option<i64> {
+    let (negative, digits) = match argument.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, argument),
+    };
+
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse::<i64>().ok()?,
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+fn parse_bytes(argument: &str) -> Option<Vec<u8>> {
+    let digits = argument
+        .strip_prefix("0x")
+        .unwrap_or(argument)
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect::<Vec<char>>();
+
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok())
+        .collect()
+}
+
+/// Returns what was expected on failure
+fn coerce(parameter: &CommandParameter, argument: &str) -> Result<TypedValue, String> {
+    match parameter.value_type.as_str() {
+        "string" => Ok(TypedValue::String(argument.to_string())),
+        "number" => {
+            let value = argument
+                .parse::<f64>()
+                .ok()
+                .filter(|v| v.is_finite())
+                .ok_or("a number")?;
+            in_range(parameter, value)?;
+
+            Ok(TypedValue::Number(value))
+        }
+        "integer" => {
+            let value = parse_integer(argument).ok_or("an integer")?;
+            in_range(parameter, value as f64)?;
+
+            Ok(TypedValue::Integer(value))
+        }
+        "boolean" => match argument.to_ascii_lowercase().as_str() {
+            "true" | "1" | "on" | "yes" => Ok(TypedValue::Boolean(true)),
+            "false" | "0" | "off" | "no" => Ok(TypedValue::Boolean(false)),
+            _ => Err("true or false".to_string()),
+        },
+        "enum" => {
+            if parameter.options.iter().any(|o| o == argument) {
+                Ok(TypedValue::String(argument.to_string()))
+            } else {
+                Err(format!("one of {}", parameter.options.join(", ")))
+            }
+        }
+        "hex" => parse_bytes(argument)
+            .map(TypedValue::Bytes)
+            .ok_or_else(|| "hex bytes".to_string()),
+        other => Err(format!("a supported parameter type, not {}", other)),
+    }
+}
+
+#[derive(Default)]
+struct DeviceCommands {
+    syntax: CommandSyntax,
+    schema: Option<Arc<CommandSchema>>,
+}
+
+///
+/// The schema of every device a project with a workspace is attached to,
+/// and the syntax every open device writes its commands in
+///
+#[derive(Default)]
+pub struct CommandSchemas {
+    devices: RwLock<HashMap<u64, DeviceCommands>>,
+}
+
+impl CommandSchemas {
+    pub fn set(&self, device: u64, schema: CommandSchema) {
+        self.devices.write().unwrap().entry(device).or_default().schema = Some(Arc::new(schema));
+    }
+
+    pub fn get(&self, device: u64) -> Option<Arc<CommandSchema>> {
+        self.devices.read().unwrap().get(&device)?.schema.clone()
+    }
+
+    /// Set from the `commands` section of the config a device was opened with
+    pub fn set_syntax(&self, device: u64, syntax: CommandSyntax) {
+        self.devices.write().unwrap().entry(device).or_default().syntax = syntax;
+    }
+
+    pub fn syntax(&self, device: u64) -> CommandSyntax {
+        self.devices
+            .read()
+            .unwrap()
+            .get(&device)
+            .map(|d| d.syntax.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandSchema, TypedValue};
+    use crate::command::Command;
+    use crate::workspace::CommandDefinition;
+    use serde_json::json;
+
+    fn schema() -> CommandSchema {
+        let definition: CommandDefinition = serde_json::from_value(json!({
+            "id": 0,
+            "name": "motor",
+            "displayName": "Motor",
+            "icon": "",
+            "parameters": [
+                {"index": 3, "displayName": "Mask", "type": "hex"},
+                {"index": 0, "displayName": "Speed", "type": "number", "min": 0, "max": 100},
+                {"index": 1, "displayName": "Mode", "type": "enum", "options": ["coast", "brake"]},
+                {"index": 2, "displayName": "Enabled", "type": "boolean"},
+            ]
+        }))
+        .unwrap();
+
+        CommandSchema::new(&[definition])
+    }
+
+    fn command(action: &str, arguments: &[&str]) -> Command {
+        Command {
+            action: action.to_string(),
+            arguments: arguments.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = schema();
+
+        let typed = schema
+            .validate(&command("motor", &["42.5", "brake", "on", "0a ff"]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            typed.arguments,
+            vec![
+                TypedValue::Number(42.5),
+                TypedValue::String("brake".to_string()),
+                TypedValue::Boolean(true),
+                TypedValue::Bytes(vec![0x0a, 0xff]),
+            ]
+        );
+
+        assert!(schema.validate(&command("ping", &[])).is_none());
+        assert!(schema.validate(&command("motor", &["42"])).unwrap().is_err());
+        assert!(schema.validate(&command("motor", &["101", "brake", "on", "00"])).unwrap().is_err());
+        assert!(schema.validate(&command("motor", &["1", "spin", "on", "00"])).unwrap().is_err());
+        assert!(schema.validate(&command("motor", &["1", "brake", "maybe", "00"])).unwrap().is_err());
+        assert!(schema.validate(&command("motor", &["1", "brake", "on", "0f0"])).unwrap().is_err());
+    }
+}
@@ -0,0 +1,190 @@
+use crate::command::CommandParser;
+use crate::device::serial;
+use crate::err::{Error, ErrorKind};
+use serde::Serialize;
+use serialport::{DataBits, Parity, StopBits};
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CANDIDATES: &[u32] = &[
+    9600, 19200, 38400, 57600, 115200, 230400, 460800, 921600, 1000000, 2000000,
+];
+
+/// Below this many bytes a sample is too small to be sure about
+const CONFIDENT_BYTES: usize = 32;
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BaudScore {
+    pub baud_rate: u32,
+    /// Higher is more likely, 0 when nothing readable was received
+    pub score: f64,
+    pub bytes: usize,
+    /// Share of printable ASCII, including whitespace
+    pub printable: f64,
+    pub lines: usize,
+    pub commands: usize,
+}
+
+fn is_printable(b: u8) -> bool {
+    (0x20..=0x7e).contains(&b) || b == b'\n' || b == b'\r' || b == b'\t'
+}
+
+///
+/// Scores a sample received at `baud_rate`. At the wrong rate a UART
+/// mostly produces non-ASCII garbage, and framing errors typically come
+/// through as 0x00 or 0xFF, so those are penalised on top.
+///
+pub fn score(baud_rate: u32, data: &[u8]) -> BaudScore {
+    let bytes = data.len();
+
+    let mut result = BaudScore {
+        baud_rate,
+        score: 0.0,
+        bytes,
+        printable: 0.0,
+        lines: data.iter().filter(|b| **b == b'\n').count(),
+        commands: 0,
+    };
+
+    if bytes == 0 {
+        return result;
+    }
+
+    let mut parser = CommandParser::new();
+    if parser.extend(data).is_ok() {
+        while let Some(parsed) = parser.parse() {
+            if parsed.is_ok() {
+                result.commands += 1;
+            }
+        }
+    }
+
+    let printable = data.iter().filter(|b| is_printable(**b)).count() as f64 / bytes as f64;
+    let framing = data.iter().filter(|b| **b == 0x00 || **b == 0xff).count() as f64 / bytes as f64;
+
+    let mut score = 0.6 * printable - 0.5 * framing;
+    if result.lines > 0 {
+        score += 0.2;
+    }
+    if result.commands > 0 {
+        score += 0.2;
+    }
+
+    result.printable = printable;
+    result.score = score.max(0.0) * bytes.min(CONFIDENT_BYTES) as f64 / CONFIDENT_BYTES as f64;
+
+    result
+}
+
+/// Best first, ties go to the faster rate
+pub fn rank(mut scores: Vec<BaudScore>) -> Vec<BaudScore> {
+    scores.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.baud_rate.cmp(&a.baud_rate))
+    });
+
+    scores
+}
+
+///
+/// Listens on `port` at each candidate rate for `listen` and ranks the rates
+/// by how plausible the received bytes are. The port must not be open.
+///
+pub fn detect(
+    port: &str,
+    candidates: &[u32],
+    listen: Duration,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+) -> Result<Vec<BaudScore>, Error> {
+    let mut scores = Vec::new();
+
+    for baud_rate in candidates.iter().copied().filter(|b| *b > 0) {
+        let mut serial_port = serialport::new(port, baud_rate)
+            .timeout(Duration::from_millis(20))
+            .data_bits(data_bits)
+            .parity(parity)
+            .stop_bits(stop_bits)
+            .open()
+            .map_err(|e| {
+                Error::new(
+                    ErrorKind::SerialError,
+                    format!("Failed to open serial port. {}", e.description),
+                )
+            })?;
+
+        // Drop anything received at the previous rate
+        let _ = serial_port.clear(serialport::ClearBuffer::Input);
+
+        let start = Instant::now();
+        let mut data = Vec::new();
+        let mut buf = [0u8; 1024];
+
+        while start.elapsed() < listen {
+            match serial_port.read(&mut buf) {
+                Ok(len) => data.extend_from_slice(&buf[..len]),
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        scores.push(score(baud_rate, &data));
+    }
+
+    Ok(rank(scores))
+}
+
+///
+/// Ranks candidate rates for a port, listening with the data bits, parity
+/// and stop bits of its config, 8N1 unless given
+///
+#[tauri::command]
+pub async fn detect_baud(
+    port: String,
+    candidates: Option<Vec<u32>>,
+    listen: Option<u64>,
+    data_bits: Option<u8>,
+    parity: Option<String>,
+    stop_bits: Option<u8>,
+) -> Result<Vec<BaudScore>, Error> {
+    let candidates = candidates.unwrap_or_else(|| DEFAULT_CANDIDATES.to_vec());
+    let listen = Duration::from_millis(listen.unwrap_or(300));
+    let data_bits = serial::data_bits(data_bits.unwrap_or_else(serial::default_data_bits))?;
+    let parity = serial::parity(&parity.unwrap_or_else(serial::default_parity))?;
+    let stop_bits = serial::stop_bits(stop_bits.unwrap_or_else(serial::default_stop_bits))?;
+
+    // Seconds of blocking reads, one listen per candidate
+    tauri::async_runtime::spawn_blocking(move || {
+        detect(&port, &candidates, listen, data_bits, parity, stop_bits)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rank, score};
+
+    #[test]
+    fn test_score() {
+        let text = b"[log info \"boot\" main.c 12]\nmotor_speed = 10\nmotor_speed = 11\n";
+        let garbage = [0x00, 0xff, 0x8c, 0x00, 0xe3, 0xff, 0x17, 0x00].repeat(8);
+
+        let good = score(115200, text);
+        let bad = score(9600, &garbage);
+
+        assert_eq!(good.commands, 1);
+        assert_eq!(good.lines, 3);
+        assert_eq!(good.printable, 1.0);
+        assert!(good.score > 0.9);
+        assert_eq!(bad.score, 0.0);
+
+        // A couple of readable bytes are not convincing
+        assert!(score(57600, b"ok").score < 0.2);
+
+        let ranked = rank(vec![bad, score(57600, b""), good]);
+        assert_eq!(ranked[0].baud_rate, 115200);
+    }
+}
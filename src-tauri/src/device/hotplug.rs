@@ -0,0 +1,206 @@
+use crate::config::BuilderConfig;
+use crate::device::{DeviceManager, PortDescriptor};
+use crate::drive::Drive;
+use crate::err::Error;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::generate_handler;
+use tauri::ipc::Channel;
+
+/// How often a watcher enumerates ports, enumeration is not free
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+static WATCH_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", content = "data")]
+pub enum PortEvent {
+    PortAdded(PortDescriptor),
+    PortRemoved(PortDescriptor),
+}
+
+/// Active watchers by id, cleared to stop one
+pub type PortWatchers = Arc<Mutex<HashMap<u64, Arc<AtomicBool>>>>;
+
+/// Filled in by the thread enumerating ports
+type Enumeration = Arc<Mutex<Option<Vec<PortDescriptor>>>>;
+
+///
+/// Changes between two enumerations. A port that is still there but now
+/// describes a different adapter counts as removed and added.
+///
+fn diff(old: &[PortDescriptor], new: &[PortDescriptor]) -> Vec<PortEvent> {
+    let removed = old
+        .iter()
+        .filter(|p| !new.contains(p))
+        .map(|p| PortEvent::PortRemoved(p.clone()));
+
+    let added = new
+        .iter()
+        .filter(|p| !old.contains(p))
+        .map(|p| PortEvent::PortAdded(p.clone()));
+
+    removed.chain(added).collect()
+}
+
+///
+/// Diffs the enumeration of one device manager and reports changes. The
+/// first pass reports every present port as added.
+///
+struct PortWatcher {
+    id: u64,
+    manager: Arc<dyn DeviceManager>,
+    channel: Channel<PortEvent>,
+    active: Arc<AtomicBool>,
+    watchers: PortWatchers,
+    ports: Vec<PortDescriptor>,
+    /// The enumeration in progress, run on its own thread so a slow one
+    /// does not hold up other drives
+    pending: Option<Enumeration>,
+    next: Instant,
+}
+
+impl PortWatcher {
+    fn stop(&self) {
+        self.active.store(false, Ordering::Relaxed);
+        self.watchers.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl Drive for PortWatcher {
+    fn drive(&mut self) -> Result<bool, Error> {
+        if !self.active.load(Ordering::Relaxed) {
+            return Ok(false);
+        }
+
+        if let Some(pending) = &self.pending {
+            let Some(ports) = pending.lock().unwrap().take() else {
+                return Ok(true);
+            };
+            self.pending = None;
+
+            let sent = diff(&self.ports, &ports)
+                .into_iter()
+                .try_for_each(|event| self.channel.send(event));
+
+            if let Err(e) = sent {
+                self.stop();
+                return Err(e.into());
+            }
+
+            self.ports = ports;
+        }
+
+        if Instant::now() < self.next {
+            return Ok(true);
+        }
+        self.next = Instant::now() + WATCH_INTERVAL;
+
+        let pending = Enumeration::default();
+
+        thread::spawn({
+            let manager = Arc::clone(&self.manager);
+            let pending = Arc::clone(&pending);
+            move || *pending.lock().unwrap() = Some(manager.available())
+        });
+        self.pending = Some(pending);
+
+        Ok(true)
+    }
+}
+
+impl<R: tauri::Runtime> BuilderConfig<R> {
+    pub fn hotplug(self) -> BuilderConfig<R> {
+        self.register_commands(
+            generate_handler![routes::watch_ports, routes::unwatch_ports],
+            &["watch_ports", "unwatch_ports"],
+        )
+        .fold(|b| b.manage(PortWatchers::default()))
+    }
+}
+
+mod routes {
+    use crate::device::hotplug::{PortEvent, PortWatcher, PortWatchers, WATCH_ID};
+    use crate::device::DeviceManagers;
+    use crate::drive::Vehicle;
+    use crate::err::{Error, ErrorKind};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Instant;
+    use tauri::ipc::Channel;
+    use tauri::State;
+
+    #[tauri::command]
+    pub fn watch_ports(
+        sort: String,
+        channel: Channel<PortEvent>,
+        managers: State<'_, DeviceManagers>,
+        watchers: State<'_, PortWatchers>,
+        driver: State<'_, Vehicle>,
+    ) -> Result<u64, Error> {
+        let manager = managers.get(&sort).ok_or_else(|| {
+            Error::new(
+                ErrorKind::UnknownDeviceManager,
+                format!("Unknown Device type {}", sort),
+            )
+        })?;
+
+        let id = WATCH_ID.fetch_add(1, Ordering::SeqCst);
+        let active = Arc::new(AtomicBool::new(true));
+
+        watchers.lock().unwrap().insert(id, Arc::clone(&active));
+
+        driver.register(PortWatcher {
+            id,
+            manager: Arc::clone(manager),
+            channel,
+            active,
+            watchers: Arc::clone(&watchers),
+            ports: Vec::new(),
+            pending: None,
+            next: Instant::now(),
+        });
+
+        Ok(id)
+    }
+
+    #[tauri::command]
+    pub fn unwatch_ports(id: u64, watchers: State<'_, PortWatchers>) {
+        if let Some(active) = watchers.lock().unwrap().remove(&id) {
+            active.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff, PortEvent};
+    use crate::device::PortDescriptor;
+
+    #[test]
+    fn test_diff() {
+        let acm0 = PortDescriptor::named("/dev/ttyACM0");
+        let acm1 = PortDescriptor::named("/dev/ttyACM1");
+        let stlink = PortDescriptor {
+            serial_number: Some("0671FF485550755187121526".to_string()),
+            ..PortDescriptor::named("/dev/ttyACM0")
+        };
+
+        let before = vec![acm0.clone(), acm1.clone()];
+        let after = vec![stlink.clone(), acm1.clone()];
+
+        assert_eq!(
+            diff(&[], &before),
+            vec![PortEvent::PortAdded(acm0.clone()), PortEvent::PortAdded(acm1)]
+        );
+        assert_eq!(diff(&before, &before), vec![]);
+        assert_eq!(
+            diff(&before, &after),
+            vec![PortEvent::PortRemoved(acm0), PortEvent::PortAdded(stlink)]
+        );
+    }
+}
@@ -1,17 +1,425 @@
+use crate::command::CommandParser;
 use crate::config::BuilderConfig;
-use crate::device::{Device, DeviceChannel, DeviceConfig, DeviceManager, DeviceRef};
-use crate::device_pool;
-use crate::err::{Error, ErrorKind};
-use rand::prelude::SliceRandom;
+use crate::device::{
+    parse_config, Device, DeviceChannel, DeviceConfig, DeviceManager, PortDescriptor,
+};
+use crate::err::Error;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::VecDeque;
+use std::f64::consts::TAU;
 use std::io;
 use std::io::{Read, Write};
-use rand::{random, Rng};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
 
-#[derive(Deserialize, Serialize)]
+/// Longest a reader sleeps before checking in with its caller
+const READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+#[derive(Deserialize, Serialize, Default)]
 pub struct MockConfig {
     pub name: String,
+    #[serde(default)]
+    pub seed: u64,
+    /// When empty, a default mix of logs and readouts is generated
+    #[serde(default)]
+    pub generators: Vec<Generator>,
+    #[serde(default)]
+    pub responses: Vec<Response>,
+    #[serde(default)]
+    pub echo: bool,
+}
+
+///
+/// A source of lines, emitted `rate` times per second of simulated time.
+///
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Generator {
+    /// `component = value`
+    Signal {
+        component: String,
+        waveform: Waveform,
+        rate: f64,
+    },
+    /// `[LEVEL Time: t File: f Line: n] message`, picking a message at random
+    Log {
+        level: String,
+        messages: Vec<String>,
+        #[serde(default = "default_file")]
+        file: String,
+        #[serde(default)]
+        line: u32,
+        rate: f64,
+    },
+    /// `[action arguments...]`
+    Command {
+        action: String,
+        #[serde(default)]
+        arguments: Vec<String>,
+        rate: f64,
+    },
+}
+
+fn default_file() -> String {
+    "mock.rs".to_string()
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(tag = "shape", rename_all = "camelCase")]
+pub enum Waveform {
+    Sine {
+        amplitude: f64,
+        /// In Hz
+        frequency: f64,
+        #[serde(default)]
+        offset: f64,
+    },
+    /// Rises linearly from `from` to `to` every `period` seconds
+    Ramp { from: f64, to: f64, period: f64 },
+    /// Uniformly distributed in `[min, max)`
+    Noise { min: f64, max: f64 },
+    /// Alternates between `low` and `high` every half `period` seconds
+    Step { low: f64, high: f64, period: f64 },
+}
+
+impl Waveform {
+    fn sample(&self, t: f64, rng: &mut StdRng) -> f64 {
+        match self {
+            Waveform::Sine {
+                amplitude,
+                frequency,
+                offset,
+            } => offset + amplitude * (TAU * frequency * t).sin(),
+            Waveform::Ramp { from, to, period } => {
+                from + (to - from) * (t / period).fract()
+            }
+            Waveform::Noise { min, max } => {
+                if max > min {
+                    rng.gen_range(*min..*max)
+                } else {
+                    *min
+                }
+            }
+            Waveform::Step { low, high, period } => {
+                if (t / period).fract() < 0.5 {
+                    *low
+                } else {
+                    *high
+                }
+            }
+        }
+    }
+}
+
+///
+/// Replies to a written `[action ...]` command. In `reply`, `$0` is replaced
+/// with the action, `$1`.. with the arguments and `$*` with all arguments.
+///
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Response {
+    pub action: String,
+    pub reply: String,
+}
+
+impl Response {
+    fn render(&self, arguments: &[String]) -> String {
+        let mut reply = self.reply.replace("$*", &arguments.join(" "));
+
+        // Highest index first so `$1` does not clobber `$10`
+        for (i, argument) in arguments.iter().enumerate().rev() {
+            reply = reply.replace(&format!("${}", i + 1), argument);
+        }
+
+        reply.replace("$0", &self.action)
+    }
+}
+
+fn default_generators() -> Vec<Generator> {
+    const LEVELS: &[&str] = &["ERROR", "WARN", "INFO", "DEBUG"];
+    const MESSAGES: &[&str] = &[
+        "Failed to establish database connection: timeout.",
+        "User login attempt failed: invalid credentials.",
+        "Request processed successfully.",
+        "Cache hit for key: user_session_123",
+        "Starting background task: data_cleanup",
+        "High memory usage detected.",
+    ];
+    const COMPONENTS: &[&str] = &[
+        "motor_speed",
+        "fan_level",
+        "fan_speed",
+        "torque",
+        "battery_level",
+        "ground_speed",
+    ];
+
+    let logs = LEVELS.iter().enumerate().map(|(i, level)| Generator::Log {
+        level: level.to_string(),
+        messages: MESSAGES.iter().map(|m| m.to_string()).collect(),
+        file: "core/engine.rs".to_string(),
+        line: 10 + 37 * i as u32,
+        rate: 2.0,
+    });
+
+    let readouts = COMPONENTS.iter().map(|component| Generator::Signal {
+        component: component.to_string(),
+        waveform: Waveform::Noise { min: 0.0, max: 1.0 },
+        rate: 3.0,
+    });
+
+    logs.chain(readouts).collect()
+}
+
+struct Schedule {
+    generator: Generator,
+    /// Number of emissions so far
+    count: u64,
+}
+
+impl Schedule {
+    fn rate(&self) -> f64 {
+        match &self.generator {
+            Generator::Signal { rate, .. } => *rate,
+            Generator::Log { rate, .. } => *rate,
+            Generator::Command { rate, .. } => *rate,
+        }
+    }
+
+    /// Simulated time of the next emission, in seconds
+    fn next(&self) -> f64 {
+        self.count as f64 / self.rate()
+    }
+
+    fn render(&self, t: f64, rng: &mut StdRng) -> String {
+        match &self.generator {
+            Generator::Signal {
+                component,
+                waveform,
+                ..
+            } => format!("{component} = {}\n", waveform.sample(t, rng)),
+            Generator::Log {
+                level,
+                messages,
+                file,
+                line,
+                ..
+            } => {
+                let message = if messages.is_empty() {
+                    ""
+                } else {
+                    messages[rng.gen_range(0..messages.len())].as_str()
+                };
+
+                format!("[{level} Time: {} File: {file} Line: {line}] {message}\n", t as u64)
+            }
+            Generator::Command {
+                action, arguments, ..
+            } => {
+                if arguments.is_empty() {
+                    format!("[{action}]\n")
+                } else {
+                    format!("[{action} {}]\n", arguments.join(" "))
+                }
+            }
+        }
+    }
+}
+
+/// Most lines one generator emits per advance. A generator that fell
+/// further behind, or emits faster than anything can read, skips ahead.
+const MAX_BURST: usize = 1000;
+
+struct Simulation {
+    rng: StdRng,
+    schedules: Vec<Schedule>,
+    output: VecDeque<u8>,
+    parser: CommandParser,
+}
+
+impl Simulation {
+    fn advance(&mut self, now: f64) {
+        let mut due = Vec::new();
+
+        for (i, schedule) in self.schedules.iter_mut().enumerate() {
+            let rate = schedule.rate();
+            if !rate.is_finite() || rate <= 0.0 {
+                continue;
+            }
+
+            let mut emitted = 0;
+
+            while schedule.next() <= now {
+                if emitted == MAX_BURST {
+                    schedule.count = (now * rate).floor() as u64 + 1;
+                    break;
+                }
+
+                due.push((schedule.next(), i));
+                schedule.count += 1;
+                emitted += 1;
+            }
+        }
+
+        due.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        for (t, i) in due {
+            let line = self.schedules[i].render(t, &mut self.rng);
+            self.output.extend(line.as_bytes());
+        }
+    }
+
+    fn drain(&mut self, buf: &mut [u8]) -> usize {
+        let len = buf.len().min(self.output.len());
+        for (i, b) in self.output.drain(..len).enumerate() {
+            buf[i] = b;
+        }
+
+        len
+    }
+
+    /// Simulated time of the next emission, if anything is scheduled
+    fn next_due(&self) -> Option<f64> {
+        self.schedules
+            .iter()
+            .filter(|s| s.rate().is_finite() && s.rate() > 0.0)
+            .map(|s| s.next())
+            .min_by(|a, b| a.total_cmp(b))
+    }
+}
+
+///
+/// Blocks until the simulation emits something, in short steps.
+///
+struct MockReader {
+    start: Instant,
+    simulation: Arc<Mutex<Simulation>>,
+}
+
+impl Read for MockReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let now = self.start.elapsed().as_secs_f64();
+
+        let mut simulation = self.simulation.lock().unwrap();
+        simulation.advance(now);
+
+        if !simulation.output.is_empty() {
+            return Ok(simulation.drain(buf));
+        }
+
+        let wait = simulation
+            .next_due()
+            .map_or(READ_TIMEOUT, |t| Duration::from_secs_f64((t - now).max(0.0)))
+            .min(READ_TIMEOUT);
+        drop(simulation);
+
+        sleep(wait);
+
+        Ok(0)
+    }
+}
+
+///
+/// A deterministic, seeded simulation of a device. Every emission is rendered
+/// at its own simulated timestamp and in time order, so the output does not
+/// depend on how often the channel is polled.
+///
+pub struct MockChannel {
+    start: Instant,
+    responses: Vec<Response>,
+    echo: bool,
+    simulation: Arc<Mutex<Simulation>>,
+}
+
+impl MockChannel {
+    pub fn new(config: &MockConfig) -> MockChannel {
+        let generators = if config.generators.is_empty() {
+            default_generators()
+        } else {
+            config.generators.clone()
+        };
+
+        MockChannel {
+            start: Instant::now(),
+            responses: config.responses.clone(),
+            echo: config.echo,
+            simulation: Arc::new(Mutex::new(Simulation {
+                rng: StdRng::seed_from_u64(config.seed),
+                schedules: generators
+                    .into_iter()
+                    .map(|generator| Schedule { generator, count: 0 })
+                    .collect(),
+                output: VecDeque::new(),
+                parser: CommandParser::new(),
+            })),
+        }
+    }
+
+    fn simulate(&self) -> MutexGuard<'_, Simulation> {
+        let mut simulation = self.simulation.lock().unwrap();
+        simulation.advance(self.start.elapsed().as_secs_f64());
+
+        simulation
+    }
+}
+
+impl Read for MockChannel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.simulate().drain(buf))
+    }
+}
+
+impl Write for MockChannel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut simulation = self.simulation.lock().unwrap();
+
+        if self.echo {
+            simulation.output.extend(buf);
+        }
+
+        simulation.parser.extend(buf).map_err(|e| io::Error::other(e.message))?;
+
+        while let Some(parsed) = simulation.parser.parse() {
+            let Ok(command) = parsed else {
+                continue;
+            };
+
+            for response in self.responses.iter().filter(|r| r.action == command.action) {
+                let mut reply = response.render(&command.arguments);
+                if !reply.ends_with('\n') {
+                    reply.push('\n');
+                }
+
+                simulation.output.extend(reply.as_bytes());
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl DeviceChannel for MockChannel {
+    fn available(&self) -> io::Result<usize> {
+        Ok(self.simulate().output.len())
+    }
+
+    fn reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(MockReader {
+            start: self.start,
+            simulation: Arc::clone(&self.simulation),
+        }))
+    }
+
+    fn close(&mut self) {
+        // Nothing to do
+    }
 }
 
 impl DeviceConfig for MockConfig {
@@ -29,29 +437,19 @@ impl DeviceManager for MockDeviceManager {
         "mock"
     }
 
-    fn open(&self, config: Value) -> Result<DeviceRef, Error> {
-        let config = serde_json::from_value::<MockConfig>(config).map_err(|_| {
-            Error::new(
-                ErrorKind::InvalidConfig,
-                "Internal error: the config passed to this handler is not valid",
-            )
-        })?;
+    fn connect(&self, config: Value) -> Result<Device, Error> {
+        let config = parse_config::<MockConfig>(config)?;
 
-        let channel = Box::new(MockChannel {});
-        let device = Device::new(
+        let channel = Box::new(MockChannel::new(&config));
+        Ok(Device::new(
             config.name.clone(),
             channel,
             Box::new(config)
-        );
-
-        let reference = DeviceRef::new();
-        device_pool!().register(device, &reference);
-
-        Ok(reference)
+        ))
     }
 
-    fn available(&self) -> Vec<String> {
-        vec!["mock".to_string()]
+    fn available(&self) -> Vec<PortDescriptor> {
+        vec![PortDescriptor::named("mock")]
     }
 }
 
@@ -61,123 +459,88 @@ impl<R: tauri::Runtime> BuilderConfig<R> {
     }
 }
 
-pub struct MockChannel {}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl Read for MockChannel {
-    fn read(&mut self, mut buf: &mut [u8]) -> io::Result<usize> {
-        // buf.write(format!("idk$={}\n", random::<f64>()).as_bytes())?;
-        // buf.write(format!("poop$={}\n", random::<f64>()).as_bytes())?;
-        // buf.write("[ERROR Time: 1678886400 File: database/connect.ts Line: 121] Failed to establish database connection: timeout.\n".as_bytes())?;
-
-        let mut rng = rand::thread_rng();
-
-        // if rng.gen_bool(0.0001) {
-        //     return Err(io::Error::new(io::ErrorKind::Other, "mock channel closed"))
-        // }
-
-        // Static data for generating varied logs
-        const LEVELS: &[&str] = &["ERROR", "WARN", "INFO", "DEBUG"];
-        const FILES: &[&str] = &["src/main.rs", "database/connect.ts", "api/user.rs", "core/engine.rs"];
-        const MESSAGES: &[&str] = &[
-            "Failed to establish database connection: timeout.",
-            "User login attempt failed: invalid credentials.",
-            "Request processed successfully.",
-            "Cache hit for key: user_session_123",
-            "Starting background task: data_cleanup",
-            "High memory usage detected.",
-        ];
-
-        // Randomly decide which log format to use
-        let log_line = if rng.gen_bool(0.7) {
-            // 70% chance of a standard formatted log
-            let level = LEVELS[rng.gen_range(0..LEVELS.len())];
-            let file = FILES[rng.gen_range(0..FILES.len())];
-            let line = rng.gen_range(10..300);
-            let timestamp = 1678886400 + rng.gen_range(0..86400); // Random time
-            let message = MESSAGES[rng.gen_range(0..MESSAGES.len())];
-
-            format!("[{level} Time: {timestamp} File: {file} Line: {line}] {message}\n")
-        } else {
-            const COMPONENTS: &[&str] = &[
-                "motor_speed",
-                "fan_level",
-                "fan_speed",
-                "torque",
-                "battery_level",
-                "ground_speed"
-            ];
-
-            let key = COMPONENTS[rng.gen_range(0..COMPONENTS.len())];
-            format!("{key} = {}\n", random::<f64>())
-        };
+    fn config(seed: u64) -> MockConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "ecu",
+            "seed": seed,
+            "generators": [
+                {
+                    "type": "signal",
+                    "component": "motor_speed",
+                    "rate": 10.0,
+                    "waveform": {"shape": "noise", "min": 0.0, "max": 100.0}
+                },
+                {
+                    "type": "command",
+                    "action": "heartbeat",
+                    "rate": 1.0
+                }
+            ],
+            "responses": [{"action": "ping", "reply": "[pong $1]"}]
+        }))
+        .unwrap()
+    }
 
-        // Write the generated string as bytes into the buffer
-        // This will return Ok(bytes_written) or Err
-        buf.write(log_line.as_bytes())
-        // if buf.is_empty() {
-        //     return Ok(0);
-        // }
-        //
-        // const ACTIONS: &[&str] = &[
-        //     "Initialized system",
-        //     "Connecting to server",
-        //     "Disconnected",
-        //     "Performing handshake",
-        //     "Reading config",
-        //     "Updating cache",
-        //     "Flushing buffer",
-        //     "Starting background task",
-        //     "Reconnecting",
-        //     "Writing data",
-        //     "Shutting down",
-        //     "Compiling resources",
-        //     "Processing request",
-        //     "Authenticating user",
-        //     "Checking status",
-        //     "Cleaning up temporary files",
-        //     "Validating input",
-        //     "Saving state",
-        //     "Restarting service",
-        //     "Completed successfully",
-        // ];
-        //
-        // let mut rng = rand::thread_rng();
-        // let mut out = Vec::with_capacity(buf.len());
-        //
-        // while out.len() + 6 < buf.len() {
-        //     // "[log ]\n" = 6 chars minimum
-        //     let msg = ACTIONS.choose(&mut rng).unwrap();
-        //     let log_line = format!("[log {}]\n", msg);
-        //
-        //     // Stop before writing if it won’t fit
-        //     if out.len() + log_line.len() > buf.len() {
-        //         break;
-        //     }
-        //
-        //     out.extend_from_slice(log_line.as_bytes());
-        // }
-        //
-        // buf[..out.len()].copy_from_slice(&out);
+    fn output(channel: &MockChannel, now: f64) -> String {
+        let mut simulation = channel.simulation.lock().unwrap();
+        simulation.advance(now);
 
+        String::from_utf8(simulation.output.drain(..).collect()).unwrap()
     }
-}
 
-impl Write for MockChannel {
-    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
-        Ok(0)
+    #[test]
+    fn test_seeded_output_is_deterministic() {
+        let a = MockChannel::new(&config(7));
+        let b = MockChannel::new(&config(7));
+
+        // Polling in different steps yields the same stream
+        let first = output(&a, 0.5) + &output(&a, 1.0);
+        let second = output(&b, 1.0);
+
+        assert_eq!(first, second);
+        assert_eq!(first.matches("motor_speed = ").count(), 11);
+        assert_eq!(first.matches("[heartbeat]\n").count(), 2);
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+    #[test]
+    fn test_waveforms() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let sine = Waveform::Sine { amplitude: 2.0, frequency: 1.0, offset: 1.0 };
+        assert!((sine.sample(0.25, &mut rng) - 3.0).abs() < 1e-9);
+
+        let ramp = Waveform::Ramp { from: 0.0, to: 10.0, period: 2.0 };
+        assert_eq!(ramp.sample(1.0, &mut rng), 5.0);
+
+        let step = Waveform::Step { low: 0.0, high: 1.0, period: 1.0 };
+        assert_eq!(step.sample(0.25, &mut rng), 0.0);
+        assert_eq!(step.sample(0.75, &mut rng), 1.0);
     }
-}
 
-impl DeviceChannel for MockChannel {
-    fn available(&self) -> std::io::Result<usize> {
-        Ok(256)
+    #[test]
+    fn test_responses() {
+        let mut channel = MockChannel::new(&config(0));
+        channel.write_all(b"[ping 42]\n").unwrap();
+
+        assert_eq!(output(&channel, -1.0), "[pong 42]\n");
     }
 
-    fn close(&mut self) {
-        // Nothing to do
+    #[test]
+    fn test_burst_is_bounded() {
+        let config: MockConfig = serde_json::from_value(serde_json::json!({
+            "name": "flood",
+            "generators": [{"type": "command", "action": "x", "rate": 1e6}]
+        }))
+        .unwrap();
+        let channel = MockChannel::new(&config);
+
+        assert_eq!(output(&channel, 60.0).lines().count(), MAX_BURST);
+        // Caught up with the present, not still working off the backlog
+        assert_eq!(output(&channel, 60.0).lines().count(), 0);
+        assert_eq!(output(&channel, 60.0001).lines().count(), 100);
     }
 }
@@ -1,4 +1,6 @@
+use crate::any::CoerceAny;
 use crate::config::{BuilderConfig, Configuration};
+use crate::device::sequence::SequenceStep;
 use crate::device_pool;
 use crate::err::{Error, ErrorKind};
 use serde::de::DeserializeOwned;
@@ -7,10 +9,20 @@ use serde_json::Value;
 use std::collections::HashMap;
 use std::io::{Read, Write};
 use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
 use tauri::{generate_handler, Builder, Runtime};
+use tokio::sync::Mutex as AsyncMutex;
 
+mod baud;
+pub mod hotplug;
 mod mock;
+#[cfg(unix)]
+mod pty;
+mod replay;
+pub mod sequence;
 pub mod serial;
+mod tcp;
+mod udp;
 
 ///
 /// A representation of all currently open sockets
@@ -94,6 +106,16 @@ pub mod pool {
             None
         }
 
+        ///
+        /// Swaps in a freshly connected device behind an existing reference,
+        /// handing back the one it replaced
+        ///
+        pub fn replace(&mut self, reference: &DeviceRef, device: Device) -> Option<Device> {
+            self.devices
+                .get_mut(&reference.id)
+                .and_then(|inner| inner.device.replace(device))
+        }
+
         pub fn rc(&mut self, reference: &DeviceRef) -> u8 {
             if let Some(inner) = self.devices.get_mut(&reference.id) {
                 inner.ref_counter
@@ -106,7 +128,17 @@ pub mod pool {
         where
             F: FnOnce(&mut Device) -> T,
         {
-            if let Some(inner) = self.devices.get_mut(&reference.id) {
+            self.use_id(reference.id, block)
+        }
+
+        ///
+        /// For code that must not hold a reference, like reader threads
+        ///
+        pub fn use_id<F, T>(&mut self, id: RefId, block: F) -> Option<T>
+        where
+            F: FnOnce(&mut Device) -> T,
+        {
+            if let Some(inner) = self.devices.get_mut(&id) {
                 if let Some(x) = inner.device.as_mut() {
                     Some(block(x))
                 } else {
@@ -195,7 +227,95 @@ impl DeviceRef {
 
     pub fn close(&mut self) {
         if let Some(mut d) = device_pool!().close(self) {
-            d.channel.close()
+            d.close()
+        }
+    }
+
+    ///
+    /// Reconnects this reference to a new device, closing the old one.
+    /// Returns false if the reference is no longer in the pool.
+    ///
+    pub fn replace(&self, device: Device) -> bool {
+        let mut pool = device_pool!();
+
+        if pool.rc(self) == 0 {
+            return false;
+        }
+
+        if let Some(mut old) = pool.replace(self, device) {
+            old.close();
+        }
+
+        true
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PortMatch {
+    /// Reopen the same port name
+    #[default]
+    Port,
+    /// Reopen whichever port the adapter with the same USB serial number shows up as
+    SerialNumber,
+}
+
+///
+/// Opt-in policy for reopening a device that dropped out, e.g. a USB-serial
+/// adapter that browned out.
+///
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ReconnectPolicy {
+    /// Milliseconds between attempts
+    #[serde(default = "default_reconnect_interval")]
+    pub interval: u64,
+    /// Gives up after this many attempts, retries forever if unset
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+    #[serde(default, rename = "match")]
+    pub match_by: PortMatch,
+}
+
+fn default_reconnect_interval() -> u64 {
+    1000
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum PortKind {
+    Usb,
+    Pci,
+    Bluetooth,
+    Other,
+}
+
+///
+/// A port a device manager can open, with whatever the OS reports about
+/// the hardware behind it.
+///
+#[derive(Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PortDescriptor {
+    pub name: String,
+    pub kind: PortKind,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+impl PortDescriptor {
+    /// A port nothing more is known about
+    pub fn named<T: Into<String>>(name: T) -> PortDescriptor {
+        PortDescriptor {
+            name: name.into(),
+            kind: PortKind::Other,
+            vid: None,
+            pid: None,
+            serial_number: None,
+            manufacturer: None,
+            product: None,
         }
     }
 }
@@ -204,24 +324,65 @@ pub trait DeviceConfig: Send + Sync {
     fn name(&self) -> String;
 
     fn serialize(&self) -> Value;
+
+    fn reconnect(&self) -> Option<ReconnectPolicy> {
+        None
+    }
+
+    fn sequence(&self, _name: &str) -> Option<Vec<SequenceStep>> {
+        None
+    }
+
+    /// Sequence to run whenever the device is opened, including reconnects
+    fn on_open(&self) -> Option<Vec<SequenceStep>> {
+        None
+    }
+
+    /// Where other programs reach the device, if it was made up on open
+    fn address(&self) -> Option<String> {
+        None
+    }
 }
 
 pub trait DeviceManager: Send + Sync {
     fn sort(&self) -> &'static str;
 
-    fn open(&self, config: Value) -> Result<DeviceRef, Error>;
+    ///
+    /// Opens a device from its config. Also used to reopen a device that
+    /// dropped out, with the config it serialized to.
+    ///
+    fn connect(&self, config: Value) -> Result<Device, Error>;
+
+    fn open(&self, config: Value) -> Result<DeviceRef, Error> {
+        let device = self.connect(config)?;
+
+        let reference = DeviceRef::new();
+
+        device_pool!().register(device, &reference);
+
+        Ok(reference)
+    }
 
-    fn available(&self) -> Vec<String>;
+    fn available(&self) -> Vec<PortDescriptor>;
 }
 
-pub type DeviceManagers = HashMap<String, Box<dyn DeviceManager>>;
+pub type DeviceManagers = HashMap<String, Arc<dyn DeviceManager>>;
+
+///
+/// Deserializes the config passed to a device manager, surfacing the serde
+/// message (which names the offending field) as an `InvalidConfig` error.
+///
+pub(crate) fn parse_config<T: DeserializeOwned>(config: Value) -> Result<T, Error> {
+    serde_json::from_value(config)
+        .map_err(|e| Error::new(ErrorKind::InvalidConfig, format!("Invalid device config: {}", e)))
+}
 
 ///
 /// Configuration for device managers
 ///
 
 struct DeviceManagerConfig {
-    device_managers: HashMap<String, Box<dyn DeviceManager>>,
+    device_managers: DeviceManagers,
 }
 
 impl<R: Runtime> Configuration<R> for DeviceManagerConfig {
@@ -239,20 +400,91 @@ impl<R: Runtime> BuilderConfig<R> {
         })
         .get_config::<DeviceManagerConfig>("device_manager", |mut c| {
             c.device_managers
-                .insert(manager.sort().to_string(), manager);
+                .insert(manager.sort().to_string(), Arc::from(manager));
             c
         })
     }
 }
 
+#[cfg(not(unix))]
+impl<R: Runtime> BuilderConfig<R> {
+    /// Pseudo-terminals are only available on unix hosts
+    pub fn pty(self) -> BuilderConfig<R> {
+        self
+    }
+}
+
 mod routes {
-    use crate::device::DeviceManagers;
+    use crate::device::{run_sequence, Device, DeviceManagers, DeviceRef, PortDescriptor};
+    use crate::err::{Error, ErrorKind};
+    use std::time::Duration;
     use tauri::State;
 
     #[tauri::command]
-    pub fn device_available(sort: String, manager: State<DeviceManagers>) -> Vec<String> {
+    pub fn device_available(sort: String, manager: State<DeviceManagers>) -> Vec<PortDescriptor> {
         manager.get(&sort).map_or(Vec::new(), |m| m.available())
     }
+
+    fn use_open<T>(
+        reference: &DeviceRef,
+        block: impl FnOnce(&mut Device) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        reference
+            .use_device(block)
+            .unwrap_or_else(|| Err(Error::new(ErrorKind::NoSuchDevice, "This device is not open.")))
+    }
+
+    ///
+    /// Drives the DTR and/or RTS output lines, leaving out either keeps its level
+    ///
+    #[tauri::command]
+    pub async fn device_set_lines(
+        reference: DeviceRef,
+        dtr: Option<bool>,
+        rts: Option<bool>,
+    ) -> Result<(), Error> {
+        let lines = use_open(&reference, |d| Ok(d.lines()))?;
+        let _lines = lines.lock().await;
+
+        use_open(&reference, |d| {
+            if let Some(dtr) = dtr {
+                d.set_dtr(dtr)?;
+            }
+
+            if let Some(rts) = rts {
+                d.set_rts(rts)?;
+            }
+
+            Ok(())
+        })
+    }
+
+    ///
+    /// Sends a break for `duration` milliseconds. The wait neither blocks the
+    /// UI nor holds the device pool.
+    ///
+    #[tauri::command]
+    pub async fn device_break(reference: DeviceRef, duration: u64) -> Result<(), Error> {
+        let lines = use_open(&reference, |d| Ok(d.lines()))?;
+        let _lines = lines.lock().await;
+
+        use_open(&reference, |d| d.set_break(true))?;
+
+        tokio::time::sleep(Duration::from_millis(duration)).await;
+
+        use_open(&reference, |d| d.set_break(false))
+    }
+
+    ///
+    /// Runs a named control-line sequence from the device config, e.g. a
+    /// reset
+    ///
+    #[tauri::command]
+    pub async fn device_sequence(reference: DeviceRef, name: String) -> Result<(), Error> {
+        let steps = use_open(&reference, |d| d.sequence(&name))?;
+
+        tauri::async_runtime::spawn_blocking(move || run_sequence(reference.id(), &steps)).await?
+    }
 }
 
 ///
@@ -268,12 +500,18 @@ impl<R: Runtime> BuilderConfig<R> {
         .register_commands(
             generate_handler![
                 routes::device_available,
+                routes::device_set_lines,
+                routes::device_break,
+                routes::device_sequence,
                 // routes::device_open,
                 // routes::device_write,
                 // routes::device_close
             ],
             &[
                 "device_available",
+                "device_set_lines",
+                "device_break",
+                "device_sequence",
                 // "device_open",
                 // "device_write",
                 // "device_close",
@@ -285,12 +523,51 @@ impl<R: Runtime> BuilderConfig<R> {
 ///
 /// A raw read/write connection to a physical or virtual socket
 ///
-pub trait DeviceChannel: Send + Sync + Read + Write {
+pub trait DeviceChannel: Send + Sync + Read + Write + CoerceAny {
     fn available(&self) -> std::io::Result<usize>;
 
+    ///
+    /// A handle for a dedicated reader thread. Reads block until data
+    /// arrives, but no longer than a short timeout, after which they return
+    /// `Ok(0)` or a `TimedOut`/`WouldBlock` error so the thread can notice
+    /// that it should stop. Any other error means the device is gone.
+    ///
+    /// Once a reader is taken, incoming data belongs to it.
+    ///
+    fn reader(&mut self) -> std::io::Result<Box<dyn Read + Send>>;
+
+    fn set_dtr(&mut self, _level: bool) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn set_rts(&mut self, _level: bool) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Holds the line in the break condition while `level` is set
+    fn set_break(&mut self, _level: bool) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// The input control lines, `None` for channels that have none
+    fn control_lines(&mut self) -> std::io::Result<Option<ControlLines>> {
+        Ok(None)
+    }
+
     fn close(&mut self);
 }
 
+///
+/// Modem input lines, true when asserted
+///
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ControlLines {
+    pub cts: bool,
+    pub dsr: bool,
+    pub cd: bool,
+    pub ri: bool,
+}
+
 ///
 /// A smart pointer wrapper to the internal device, provides
 /// utilities to interact with the underlying type.
@@ -299,6 +576,9 @@ pub struct Device {
     pub name: String,
     channel: Box<dyn DeviceChannel>,
     config: Box<dyn DeviceConfig>,
+    /// Held while output lines are driven, so that sequences, breaks and
+    /// line changes do not interleave
+    lines: Arc<AsyncMutex<()>>,
 }
 
 impl Device {
@@ -311,9 +591,17 @@ impl Device {
             name,
             channel,
             config,
+            lines: Arc::new(AsyncMutex::new(())),
         }
     }
 
+    ///
+    /// Access the concrete channel, for manager specific operations
+    ///
+    pub fn channel_as<T: 'static>(&mut self) -> Option<&mut T> {
+        self.channel.as_mut().as_any().downcast_mut::<T>()
+    }
+
     pub fn read_available(&mut self) -> Result<Vec<u8>, Error> {
         let available = self.channel.available()?;
 
@@ -337,6 +625,66 @@ impl Device {
         Ok(self.channel.read(buf)?)
     }
 
+    pub fn reader(&mut self) -> Result<Box<dyn Read + Send>, Error> {
+        Ok(self.channel.reader()?)
+    }
+
+    pub fn set_dtr(&mut self, level: bool) -> Result<(), Error> {
+        Ok(self.channel.set_dtr(level)?)
+    }
+
+    pub fn set_rts(&mut self, level: bool) -> Result<(), Error> {
+        Ok(self.channel.set_rts(level)?)
+    }
+
+    pub fn set_break(&mut self, level: bool) -> Result<(), Error> {
+        Ok(self.channel.set_break(level)?)
+    }
+
+    pub fn control_lines(&mut self) -> Result<Option<ControlLines>, Error> {
+        Ok(self.channel.control_lines()?)
+    }
+
+    ///
+    /// A control-line sequence from the device config
+    ///
+    pub fn sequence(&self, name: &str) -> Result<Vec<SequenceStep>, Error> {
+        self.config.sequence(name).ok_or_else(|| {
+            Error::new(
+                ErrorKind::NoSuchSequence,
+                format!("{} has no sequence named {}", self.name, name),
+            )
+        })
+    }
+
+    pub fn on_open(&self) -> Option<Vec<SequenceStep>> {
+        self.config.on_open()
+    }
+
+    pub fn apply_step(&mut self, step: &SequenceStep) -> Result<(), Error> {
+        Ok(step.apply(self.channel.as_mut())?)
+    }
+
+    pub fn lines(&self) -> Arc<AsyncMutex<()>> {
+        Arc::clone(&self.lines)
+    }
+
+    pub fn reconnect(&self) -> Option<ReconnectPolicy> {
+        self.config.reconnect()
+    }
+
+    pub fn address(&self) -> Option<String> {
+        self.config.address()
+    }
+
+    pub fn config(&self) -> Value {
+        self.config.serialize()
+    }
+
+    pub fn close(&mut self) {
+        self.channel.close()
+    }
+
     pub fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
         Ok(self.channel.write(buf)?)
     }
@@ -346,23 +694,47 @@ impl Device {
     }
 }
 
+///
+/// Runs a control-line sequence on an open device, from a blocking thread.
+/// The device's lines stay locked throughout, the device pool is only held
+/// while a line changes and never during a wait.
+///
+pub fn run_sequence(device: u64, steps: &[SequenceStep]) -> Result<(), Error> {
+    let not_open = || Error::new(ErrorKind::NoSuchDevice, "This device is not open.");
+
+    let lines = device_pool!().use_id(device, |d| d.lines()).ok_or_else(not_open)?;
+    let _lines = lines.blocking_lock();
+
+    sequence::run(steps, |step| {
+        device_pool!()
+            .use_id(device, |d| d.apply_step(step))
+            .unwrap_or_else(|| Err(not_open()))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use crate::device::mock::{MockChannel, MockConfig};
     use crate::device::pool::{DevicePool, DEVICE_POOL};
-    use crate::device::{Device, DeviceRef};
+    use crate::device::sequence::SequenceStep;
+    use crate::device::{run_sequence, Device, DeviceRef};
     use crate::device_pool;
+    use std::thread;
+    use std::time::Duration;
 
     #[test]
     fn test_rc() {
         DEVICE_POOL.set(DevicePool::new());
 
+        let config = MockConfig {
+            name: "test".to_string(),
+            ..Default::default()
+        };
+
         let dev = Device::new(
             "test".to_string(),
-            Box::new(MockChannel {}),
-            Box::new(MockConfig {
-                name: "test".to_string(),
-            }),
+            Box::new(MockChannel::new(&config)),
+            Box::new(config),
         );
 
         let mut ref1 = DeviceRef::new();
@@ -379,4 +751,72 @@ mod tests {
         ref2.close();
         assert_eq!(ref1.rc(), 0);
     }
+
+    #[test]
+    fn test_replace() {
+        let _ = DEVICE_POOL.set(DevicePool::new());
+
+        let device = |name: &str| {
+            let config = MockConfig {
+                name: name.to_string(),
+                ..Default::default()
+            };
+
+            Device::new(name.to_string(), Box::new(MockChannel::new(&config)), Box::new(config))
+        };
+
+        let mut reference = DeviceRef::new();
+        device_pool!().register(device("first"), &reference);
+
+        assert!(reference.replace(device("second")));
+        assert_eq!(reference.use_device(|d| d.name.clone()).unwrap(), "second");
+        assert_eq!(reference.rc(), 1);
+
+        reference.close();
+        assert!(!reference.replace(device("third")));
+    }
+
+    #[test]
+    fn test_control_lines_default_to_none() {
+        let config = MockConfig::default();
+        let mut device = Device::new(
+            "mock".to_string(),
+            Box::new(MockChannel::new(&config)),
+            Box::new(config),
+        );
+
+        device.set_dtr(false).unwrap();
+        device.set_break(true).unwrap();
+        assert_eq!(device.control_lines().unwrap(), None);
+    }
+
+    #[test]
+    fn test_sequence_waits_for_lines() {
+        let _ = DEVICE_POOL.set(DevicePool::new());
+
+        let config = MockConfig::default();
+        let device = Device::new(
+            "mock".to_string(),
+            Box::new(MockChannel::new(&config)),
+            Box::new(config),
+        );
+
+        let mut reference = DeviceRef::new();
+        device_pool!().register(device, &reference);
+
+        let lines = reference.use_device(|d| d.lines()).unwrap();
+        let held = lines.blocking_lock();
+
+        let id = reference.id();
+        let sequence = thread::spawn(move || run_sequence(id, &[SequenceStep::Rts(true)]));
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!sequence.is_finished());
+
+        drop(held);
+        sequence.join().unwrap().unwrap();
+
+        reference.close();
+        assert!(run_sequence(id, &[SequenceStep::Rts(true)]).is_err());
+    }
 }
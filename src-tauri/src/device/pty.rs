@@ -0,0 +1,301 @@
+use crate::config::BuilderConfig;
+use crate::device::{
+    parse_config, Device, DeviceChannel, DeviceConfig, DeviceManager, PortDescriptor,
+};
+use crate::err::{Error, ErrorKind};
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::pty::openpty;
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+use nix::unistd::ttyname;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+const SLAVE_PLACEHOLDER: &str = "{slave}";
+const READ_TIMEOUT_MS: u16 = 100;
+
+fn exited(child: &Mutex<Option<Child>>) -> io::Result<()> {
+    if let Some(child) = child.lock().unwrap().as_mut()
+        && let Some(status) = child.try_wait()?
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            format!("PTY process exited ({})", status),
+        ));
+    }
+
+    Ok(())
+}
+
+///
+/// The master side of a pseudo-terminal pair. The slave side looks like any
+/// other serial port to the process on the other end (e.g. a host build of
+/// the firmware).
+///
+/// We keep our own handle on the slave open so that reading the master does
+/// not fail with `EIO` while no other process has it open.
+///
+struct PtyChannel {
+    master: Option<File>,
+    slave: Option<OwnedFd>,
+    slave_path: PathBuf,
+    child: Arc<Mutex<Option<Child>>>,
+    link: Option<PathBuf>,
+}
+
+struct PtyReader {
+    master: File,
+    child: Arc<Mutex<Option<Child>>>,
+}
+
+impl Read for PtyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        exited(&self.child)?;
+
+        let mut fds = [PollFd::new(self.master.as_fd(), PollFlags::POLLIN)];
+        if poll(&mut fds, READ_TIMEOUT_MS)? == 0 {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+
+        self.master.read(buf)
+    }
+}
+
+impl PtyChannel {
+    fn open(link: Option<PathBuf>) -> io::Result<PtyChannel> {
+        let pty = openpty(None, None)?;
+
+        // No echo or line discipline, the slave should behave like a UART
+        let mut termios = tcgetattr(&pty.slave)?;
+        cfmakeraw(&mut termios);
+        tcsetattr(&pty.slave, SetArg::TCSANOW, &termios)?;
+
+        let slave_path = ttyname(&pty.slave)?;
+
+        if let Some(link) = link.as_ref() {
+            if link.is_symlink() {
+                std::fs::remove_file(link)?;
+            }
+            std::os::unix::fs::symlink(&slave_path, link)?;
+        }
+
+        Ok(PtyChannel {
+            master: Some(File::from(pty.master)),
+            slave: Some(pty.slave),
+            slave_path,
+            child: Arc::new(Mutex::new(None)),
+            link,
+        })
+    }
+
+    fn spawn(&self, command: &[String]) -> io::Result<()> {
+        let slave = self.slave_path.to_string_lossy();
+        let (program, args) = command
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Empty command."))?;
+
+        let child = Command::new(program)
+            .args(args.iter().map(|a| a.replace(SLAVE_PLACEHOLDER, &slave)))
+            .env("SERIAL_DURBUGGER_PTY", slave.as_ref())
+            .stdin(Stdio::null())
+            .spawn()?;
+
+        *self.child.lock().unwrap() = Some(child);
+
+        Ok(())
+    }
+
+    fn master(&mut self) -> io::Result<&mut File> {
+        self.master
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "PTY is closed."))
+    }
+}
+
+impl Read for PtyChannel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.master()?.read(buf)
+    }
+}
+
+impl Write for PtyChannel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.master()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.master()?.flush()
+    }
+}
+
+impl DeviceChannel for PtyChannel {
+    fn available(&self) -> io::Result<usize> {
+        let master = self
+            .master
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "PTY is closed."))?;
+
+        exited(&self.child)?;
+
+        let mut available: libc::c_int = 0;
+        // SAFETY: FIONREAD writes a single c_int through the pointer
+        if unsafe { libc::ioctl(master.as_raw_fd(), libc::FIONREAD, &mut available) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(available as usize)
+    }
+
+    fn reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        Ok(Box::new(PtyReader {
+            master: self.master()?.try_clone()?,
+            child: Arc::clone(&self.child),
+        }))
+    }
+
+    fn close(&mut self) {
+        if let Some(mut child) = self.child.lock().unwrap().take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+
+        self.master.take();
+        self.slave.take();
+
+        if let Some(link) = self.link.take() {
+            let _ = std::fs::remove_file(link);
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct PtyConfig {
+    name: String,
+    #[serde(default)]
+    command: Vec<String>,
+    #[serde(default)]
+    link: Option<String>,
+    /// Set once the pseudo-terminal exists, a new one is made on every open
+    #[serde(skip_deserializing)]
+    slave: Option<String>,
+}
+
+impl DeviceConfig for PtyConfig {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn serialize(&self) -> Value {
+        serde_json::to_value(self).unwrap()
+    }
+
+    fn address(&self) -> Option<String> {
+        self.slave.clone()
+    }
+}
+
+struct PtyManager {}
+
+impl DeviceManager for PtyManager {
+    fn sort(&self) -> &'static str {
+        "pty"
+    }
+
+    fn connect(&self, config: Value) -> Result<Device, Error> {
+        let mut config: PtyConfig = parse_config(config)?;
+
+        let channel = PtyChannel::open(config.link.as_ref().map(PathBuf::from)).map_err(|e| {
+            Error::new(
+                ErrorKind::IO,
+                format!("Failed to create pseudo-terminal. {}", e),
+            )
+        })?;
+
+        if !config.command.is_empty() {
+            channel.spawn(&config.command).map_err(|e| {
+                Error::invalid_config("command", format!("failed to spawn {:?}: {}", config.command, e))
+            })?;
+        }
+
+        config.slave = Some(channel.slave_path.to_string_lossy().to_string());
+
+        Ok(Device::new(config.name.clone(), Box::new(channel), Box::new(config)))
+    }
+
+    fn available(&self) -> Vec<PortDescriptor> {
+        vec![PortDescriptor::named("pty")]
+    }
+}
+
+impl<R: tauri::Runtime> BuilderConfig<R> {
+    pub fn pty(self) -> BuilderConfig<R> {
+        self.register_device_manager(Box::new(PtyManager {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PtyChannel, PtyManager};
+    use crate::device::{DeviceChannel, DeviceManager};
+    use serde_json::json;
+    use std::io::{Read, Write};
+    use std::path::Path;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_serial_port_over_pty() {
+        let mut channel = PtyChannel::open(None).unwrap();
+
+        let mut port = serialport::new(channel.slave_path.to_string_lossy(), 115_200)
+            .timeout(Duration::from_millis(500))
+            .open()
+            .unwrap();
+
+        port.write_all(b"[log info \"hello\"]\n").unwrap();
+        sleep(Duration::from_millis(50));
+
+        let available = channel.available().unwrap();
+        assert_eq!(available, 19);
+
+        let mut buf = vec![0u8; available];
+        channel.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, b"[log info \"hello\"]\n");
+
+        channel.write_all(b"[ping]\n").unwrap();
+
+        let mut buf = [0u8; 7];
+        port.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"[ping]\n");
+
+        let mut reader = channel.reader().unwrap();
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+
+        port.write_all(b"[pong]\n").unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 7);
+
+        channel.close();
+    }
+
+    #[test]
+    fn test_slave_is_reported() {
+        // The slave is made up on open, a configured one is ignored
+        let mut device = PtyManager {}
+            .connect(json!({"name": "pty", "slave": "/dev/null"}))
+            .unwrap();
+
+        let address = device.address().unwrap();
+        assert_ne!(address, "/dev/null");
+        assert!(Path::new(&address).exists());
+
+        device.close();
+    }
+}
@@ -0,0 +1,493 @@
+use crate::capture::{CaptureReader, Direction, Record};
+use crate::config::BuilderConfig;
+use crate::device::{
+    parse_config, Device, DeviceChannel, DeviceConfig, DeviceManager, DeviceRef, PortDescriptor,
+};
+use crate::err::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use tauri::generate_handler;
+
+/// Longest a reader sleeps before checking in with its caller
+const READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+///
+/// Where playback currently is in the capture. Capture time advances with
+/// wall time (scaled by `speed`) from `anchor`, unless paused.
+///
+struct Playback {
+    index: usize,
+    offset: usize,
+    position: Duration,
+    anchor: Instant,
+    speed: f64,
+    paused: bool,
+}
+
+impl Playback {
+    fn now(&self) -> Duration {
+        if self.paused {
+            self.position
+        } else {
+            self.position + self.anchor.elapsed().mul_f64(self.speed)
+        }
+    }
+
+    fn rebase(&mut self) {
+        self.position = self.now();
+        self.anchor = Instant::now();
+    }
+
+    fn seek(&mut self, records: &[Record], position: Duration) {
+        self.position = position;
+        self.anchor = Instant::now();
+        self.index = records.partition_point(|r| r.monotonic < position);
+        self.offset = 0;
+    }
+}
+
+///
+/// The received side of a capture, shared between the channel and its reader.
+///
+struct Replay {
+    records: Vec<Record>,
+    looping: bool,
+    closed: AtomicBool,
+    playback: Mutex<Playback>,
+}
+
+impl Replay {
+    fn new(records: Vec<Record>, config: &ReplayConfig) -> Replay {
+        let records = records
+            .into_iter()
+            .filter(|r| r.direction == Direction::Rx)
+            .collect::<Vec<Record>>();
+
+        let mut playback = Playback {
+            index: 0,
+            offset: 0,
+            position: Duration::ZERO,
+            anchor: Instant::now(),
+            speed: config.speed,
+            paused: config.paused,
+        };
+        playback.seek(&records, Duration::from_millis(config.start));
+
+        Replay {
+            records,
+            looping: config.looping,
+            closed: AtomicBool::new(false),
+            playback: Mutex::new(playback),
+        }
+    }
+
+    fn duration(&self) -> Duration {
+        self.records.last().map_or(Duration::ZERO, |r| r.monotonic)
+    }
+
+    fn closed(&self) -> io::Result<()> {
+        if self.closed.load(Ordering::Relaxed) {
+            Err(io::Error::new(io::ErrorKind::NotConnected, "Replay is closed."))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn wrap(&self, playback: &mut Playback) {
+        if self.looping && playback.index >= self.records.len() && playback.now() > self.duration() {
+            playback.seek(&self.records, Duration::ZERO);
+        }
+    }
+
+    fn status(&self) -> ReplayStatus {
+        let playback = self.playback.lock().unwrap();
+
+        ReplayStatus {
+            position: playback.now().min(self.duration()).as_millis() as u64,
+            duration: self.duration().as_millis() as u64,
+            speed: playback.speed,
+            paused: playback.paused,
+        }
+    }
+
+    fn control(&self, control: ReplayControl) -> Result<(), Error> {
+        let mut playback = self.playback.lock().unwrap();
+
+        match control {
+            ReplayControl::Pause => {
+                playback.rebase();
+                playback.paused = true;
+            }
+            ReplayControl::Resume => {
+                playback.anchor = Instant::now();
+                playback.paused = false;
+            }
+            ReplayControl::Seek { position } => {
+                playback.seek(&self.records, Duration::from_millis(position));
+            }
+            ReplayControl::Speed { value } => {
+                validate_speed(value)?;
+                playback.rebase();
+                playback.speed = value;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        self.closed()?;
+
+        let mut playback = self.playback.lock().unwrap();
+        self.wrap(&mut playback);
+
+        let now = playback.now();
+        let mut written = 0;
+
+        while written < buf.len() {
+            let Some(record) = self.records.get(playback.index) else {
+                break;
+            };
+
+            if record.monotonic > now {
+                break;
+            }
+
+            let remaining = &record.data[playback.offset..];
+            let len = remaining.len().min(buf.len() - written);
+            buf[written..written + len].copy_from_slice(&remaining[..len]);
+            written += len;
+
+            if len == remaining.len() {
+                playback.index += 1;
+                playback.offset = 0;
+            } else {
+                playback.offset += len;
+            }
+        }
+
+        Ok(written)
+    }
+
+    fn available(&self) -> io::Result<usize> {
+        self.closed()?;
+
+        let mut playback = self.playback.lock().unwrap();
+        self.wrap(&mut playback);
+
+        let now = playback.now();
+
+        Ok(self.records[playback.index..]
+            .iter()
+            .take_while(|r| r.monotonic <= now)
+            .map(|r| r.data.len())
+            .sum::<usize>()
+            - playback.offset)
+    }
+
+    /// Wall time until the next record is due, if there is one and playback is running
+    fn next_due(&self) -> Option<Duration> {
+        let playback = self.playback.lock().unwrap();
+
+        if playback.paused {
+            return None;
+        }
+
+        let next = match self.records.get(playback.index) {
+            Some(record) => record.monotonic,
+            None if self.looping => self.duration(),
+            None => return None,
+        };
+
+        Some(next.saturating_sub(playback.now()).div_f64(playback.speed))
+    }
+}
+
+///
+/// Feeds the received side of a capture back with its original timing.
+///
+struct ReplayChannel {
+    replay: Arc<Replay>,
+}
+
+///
+/// Sleeps until the next record is due, in short steps so that pauses,
+/// seeks and speed changes take effect promptly.
+///
+struct ReplayReader {
+    replay: Arc<Replay>,
+}
+
+impl Read for ReplayReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.replay.read(buf)?;
+
+        if len == 0 {
+            sleep(self.replay.next_due().unwrap_or(READ_TIMEOUT).min(READ_TIMEOUT));
+        }
+
+        Ok(len)
+    }
+}
+
+impl Read for ReplayChannel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.replay.read(buf)
+    }
+}
+
+impl Write for ReplayChannel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Nothing is listening on the other end of a recording
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl DeviceChannel for ReplayChannel {
+    fn available(&self) -> io::Result<usize> {
+        self.replay.available()
+    }
+
+    fn reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        self.replay.closed()?;
+
+        Ok(Box::new(ReplayReader {
+            replay: Arc::clone(&self.replay),
+        }))
+    }
+
+    fn close(&mut self) {
+        self.replay.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct ReplayConfig {
+    name: String,
+    path: String,
+    #[serde(default = "default_speed")]
+    speed: f64,
+    #[serde(default, rename = "loop")]
+    looping: bool,
+    #[serde(default)]
+    start: u64,
+    #[serde(default)]
+    paused: bool,
+}
+
+fn default_speed() -> f64 {
+    1.0
+}
+
+fn validate_speed(speed: f64) -> Result<(), Error> {
+    if speed.is_finite() && speed > 0.0 {
+        Ok(())
+    } else {
+        Err(Error::invalid_config("speed", format!("must be a positive number, got {}", speed)))
+    }
+}
+
+impl DeviceConfig for ReplayConfig {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn serialize(&self) -> Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+struct ReplayManager {}
+
+impl DeviceManager for ReplayManager {
+    fn sort(&self) -> &'static str {
+        "replay"
+    }
+
+    fn connect(&self, config: Value) -> Result<Device, Error> {
+        let config: ReplayConfig = parse_config(config)?;
+
+        validate_speed(config.speed)?;
+
+        let records = CaptureReader::open(&config.path)
+            .and_then(|r| r.records())
+            .map_err(|e| Error::invalid_config("path", format!("cannot read {}: {}", config.path, e)))?;
+
+        let replay = Replay::new(records, &config);
+
+        if replay.records.is_empty() {
+            return Err(Error::invalid_config(
+                "path",
+                format!("{} contains no received data", config.path),
+            ));
+        }
+
+        let channel = ReplayChannel {
+            replay: Arc::new(replay),
+        };
+
+        Ok(Device::new(config.name.clone(), Box::new(channel), Box::new(config)))
+    }
+
+    fn available(&self) -> Vec<PortDescriptor> {
+        vec![PortDescriptor::named("replay")]
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum ReplayControl {
+    Pause,
+    Resume,
+    /// Capture time to jump to, in milliseconds
+    Seek { position: u64 },
+    Speed { value: f64 },
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReplayStatus {
+    position: u64,
+    duration: u64,
+    speed: f64,
+    paused: bool,
+}
+
+#[tauri::command]
+fn replay_control(
+    reference: DeviceRef,
+    control: Option<ReplayControl>,
+) -> Result<ReplayStatus, Error> {
+    reference
+        .use_device(|d| {
+            let channel = d.channel_as::<ReplayChannel>().ok_or_else(|| {
+                Error::new(ErrorKind::Unsupported, "This device is not a replay.")
+            })?;
+
+            if let Some(control) = control {
+                channel.replay.control(control)?;
+            }
+
+            Ok(channel.replay.status())
+        })
+        .unwrap_or_else(|| Err(Error::new(ErrorKind::NoSuchDevice, "This device is not open.")))
+}
+
+impl<R: tauri::Runtime> BuilderConfig<R> {
+    pub fn replay(self) -> BuilderConfig<R> {
+        self.register_device_manager(Box::new(ReplayManager {}))
+            .register_commands(generate_handler![replay_control], &["replay_control"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Replay, ReplayChannel, ReplayConfig, ReplayControl};
+    use crate::capture::{Direction, Record};
+    use crate::device::DeviceChannel;
+    use std::io::Read;
+    use std::sync::Arc;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    fn channel(speed: f64, looping: bool) -> ReplayChannel {
+        let record = |direction, ms, data: &str| Record {
+            direction,
+            monotonic: Duration::from_millis(ms),
+            unix: Duration::from_millis(ms),
+            data: data.as_bytes().to_vec(),
+        };
+
+        let replay = Replay::new(
+            vec![
+                record(Direction::Rx, 0, "first\n"),
+                record(Direction::Tx, 10, "[ping]\n"),
+                record(Direction::Rx, 1000, "second\n"),
+            ],
+            &ReplayConfig {
+                name: "replay".to_string(),
+                path: String::new(),
+                speed,
+                looping,
+                start: 0,
+                paused: false,
+            },
+        );
+
+        ReplayChannel {
+            replay: Arc::new(replay),
+        }
+    }
+
+    #[test]
+    fn test_timing() {
+        let mut channel = channel(1.0, false);
+
+        assert_eq!(channel.available().unwrap(), 6);
+
+        let mut buf = [0u8; 64];
+        let len = channel.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"first\n");
+
+        // The transmitted record is skipped and the next one is not due yet
+        assert_eq!(channel.available().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_speed_and_loop() {
+        let mut channel = channel(100.0, true);
+
+        sleep(Duration::from_millis(20));
+
+        let mut buf = [0u8; 64];
+        let len = channel.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"first\nsecond\n");
+
+        sleep(Duration::from_millis(20));
+        let len = channel.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"first\n");
+    }
+
+    #[test]
+    fn test_pause_and_seek() {
+        let mut channel = channel(1.0, false);
+
+        channel.replay.control(ReplayControl::Pause).unwrap();
+        channel.replay.control(ReplayControl::Seek { position: 500 }).unwrap();
+        assert_eq!(channel.available().unwrap(), 0);
+
+        channel.replay.control(ReplayControl::Seek { position: 1000 }).unwrap();
+        let mut buf = [0u8; 64];
+        let len = channel.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"second\n");
+
+        assert!(channel.replay.status().paused);
+        assert!(channel.replay.control(ReplayControl::Speed { value: -1.0 }).is_err());
+    }
+
+    #[test]
+    fn test_reader() {
+        let mut channel = channel(100.0, false);
+        let mut reader = channel.reader().unwrap();
+
+        let mut buf = [0u8; 64];
+        assert_eq!(reader.read(&mut buf).unwrap(), 6);
+
+        // Sleeps towards the next record instead of spinning
+        let start = std::time::Instant::now();
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert!(start.elapsed() >= Duration::from_millis(5));
+
+        channel.close();
+        assert!(reader.read(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::device::DeviceChannel;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::thread::sleep;
+use std::time::Duration;
+
+///
+/// One step of a control-line sequence, written in a device config as e.g.
+/// `{"rts": true}` or `{"wait": 100}`. Levels are logical, `true` asserts.
+///
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SequenceStep {
+    Dtr(bool),
+    Rts(bool),
+    Break(bool),
+    /// Milliseconds
+    Wait(u64),
+}
+
+impl SequenceStep {
+    /// Sets the line of a step, waits are left to `run`
+    pub fn apply(&self, channel: &mut dyn DeviceChannel) -> io::Result<()> {
+        match self {
+            SequenceStep::Dtr(level) => channel.set_dtr(*level),
+            SequenceStep::Rts(level) => channel.set_rts(*level),
+            SequenceStep::Break(level) => channel.set_break(*level),
+            SequenceStep::Wait(_) => Ok(()),
+        }
+    }
+}
+
+///
+/// Runs the steps back to back on the backend, so waits are not stretched
+/// by IPC round trips. `apply` sets a line, it should hold the device no
+/// longer than that so others can use it during waits.
+///
+pub fn run<E>(
+    steps: &[SequenceStep],
+    mut apply: impl FnMut(&SequenceStep) -> Result<(), E>,
+) -> Result<(), E> {
+    for step in steps {
+        match step {
+            SequenceStep::Wait(ms) => sleep(Duration::from_millis(*ms)),
+            step => apply(step)?,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, SequenceStep};
+    use crate::device::DeviceChannel;
+    use std::io;
+    use std::io::{Read, Write};
+    use std::time::Instant;
+
+    /// Logs line changes with the time since the sequence started
+    struct LineLog {
+        start: Instant,
+        log: Vec<(&'static str, bool, u128)>,
+    }
+
+    impl Read for LineLog {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl Write for LineLog {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl DeviceChannel for LineLog {
+        fn available(&self) -> io::Result<usize> {
+            Ok(0)
+        }
+
+        fn reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+            Err(io::ErrorKind::Unsupported.into())
+        }
+
+        fn set_dtr(&mut self, level: bool) -> io::Result<()> {
+            self.log.push(("dtr", level, self.start.elapsed().as_millis()));
+            Ok(())
+        }
+
+        fn set_rts(&mut self, level: bool) -> io::Result<()> {
+            self.log.push(("rts", level, self.start.elapsed().as_millis()));
+            Ok(())
+        }
+
+        fn close(&mut self) {}
+    }
+
+    #[test]
+    fn test_reset_sequence() {
+        let steps: Vec<SequenceStep> = serde_json::from_value(serde_json::json!([
+            {"rts": true},
+            {"wait": 100},
+            {"dtr": false},
+            {"wait": 50},
+            {"rts": false}
+        ]))
+        .unwrap();
+
+        let mut channel = LineLog {
+            start: Instant::now(),
+            log: Vec::new(),
+        };
+        run(&steps, |step| step.apply(&mut channel)).unwrap();
+
+        let lines = channel.log.iter().map(|(l, v, _)| (*l, *v)).collect::<Vec<_>>();
+        assert_eq!(lines, vec![("rts", true), ("dtr", false), ("rts", false)]);
+
+        assert!(channel.log[1].2 >= 100);
+        assert!(channel.log[2].2 >= 150);
+    }
+}
@@ -1,14 +1,22 @@
 use crate::config::BuilderConfig;
-use crate::device::{Device, DeviceChannel, DeviceConfig, DeviceManager, DeviceRef};
-use crate::device_pool;
+use crate::device::baud;
+use crate::device::sequence::SequenceStep;
+use crate::device::{
+    parse_config, ControlLines, Device, DeviceChannel, DeviceConfig, DeviceManager,
+    PortDescriptor, PortKind, PortMatch, ReconnectPolicy,
+};
 use crate::err::{Error, ErrorKind};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use serialport::SerialPort;
+use std::collections::HashMap;
+use serialport::{
+    DataBits, FlowControl, Parity, SerialPort, SerialPortInfo, SerialPortType, StopBits,
+};
 use std::io::{Read, Write};
 use std::ops::{Deref, DerefMut};
 use std::sync::Mutex;
 use std::time::Duration;
+use tauri::generate_handler;
 
 struct SerialChannel {
     port: Mutex<Option<Box<dyn SerialPort>>>,
@@ -54,6 +62,36 @@ impl DeviceChannel for SerialChannel {
         }))
     }
 
+    fn reader(&mut self) -> std::io::Result<Box<dyn Read + Send>> {
+        // Reads on the clone time out after the configured port timeout
+        let port = self.use_port(|p| p.try_clone().map_err(std::io::Error::from))?;
+
+        Ok(Box::new(port))
+    }
+
+    fn set_dtr(&mut self, level: bool) -> std::io::Result<()> {
+        self.use_port(|p| Ok(p.write_data_terminal_ready(level)?))
+    }
+
+    fn set_rts(&mut self, level: bool) -> std::io::Result<()> {
+        self.use_port(|p| Ok(p.write_request_to_send(level)?))
+    }
+
+    fn set_break(&mut self, level: bool) -> std::io::Result<()> {
+        self.use_port(|p| Ok(if level { p.set_break() } else { p.clear_break() }?))
+    }
+
+    fn control_lines(&mut self) -> std::io::Result<Option<ControlLines>> {
+        self.use_port(|p| {
+            Ok(Some(ControlLines {
+                cts: p.read_clear_to_send()?,
+                dsr: p.read_data_set_ready()?,
+                cd: p.read_carrier_detect()?,
+                ri: p.read_ring_indicator()?,
+            }))
+        })
+    }
+
     fn close(&mut self) {
         self.port.lock().unwrap().take();
     }
@@ -64,6 +102,166 @@ struct SerialConfig {
     name: String,
     pub(in crate::device::serial) baud_rate: u32,
     pub(in crate::device::serial) timeout: u64,
+    #[serde(default = "default_data_bits")]
+    pub(in crate::device::serial) data_bits: u8,
+    #[serde(default = "default_parity")]
+    pub(in crate::device::serial) parity: String,
+    #[serde(default = "default_stop_bits")]
+    pub(in crate::device::serial) stop_bits: u8,
+    #[serde(default = "default_flow_control")]
+    pub(in crate::device::serial) flow_control: String,
+    #[serde(default)]
+    reconnect: Option<ReconnectPolicy>,
+    /// USB serial number of the adapter, recorded when the port is opened
+    #[serde(default)]
+    serial_number: Option<String>,
+    #[serde(default)]
+    sequences: HashMap<String, Vec<SequenceStep>>,
+    /// Sequence to run whenever the port is opened, including reconnects
+    #[serde(default)]
+    on_open: Option<String>,
+}
+
+fn describe(port: SerialPortInfo) -> PortDescriptor {
+    let mut descriptor = PortDescriptor::named(port.port_name);
+
+    match port.port_type {
+        SerialPortType::UsbPort(info) => {
+            descriptor.kind = PortKind::Usb;
+            descriptor.vid = Some(info.vid);
+            descriptor.pid = Some(info.pid);
+            descriptor.serial_number = info.serial_number;
+            descriptor.manufacturer = info.manufacturer;
+            descriptor.product = info.product;
+        }
+        SerialPortType::PciPort => descriptor.kind = PortKind::Pci,
+        SerialPortType::BluetoothPort => descriptor.kind = PortKind::Bluetooth,
+        SerialPortType::Unknown => {}
+    }
+
+    descriptor
+}
+
+fn serial_number(port: &str) -> Option<String> {
+    serialport::available_ports()
+        .ok()?
+        .into_iter()
+        .find(|p| p.port_name == port)
+        .and_then(|p| match p.port_type {
+            SerialPortType::UsbPort(info) => info.serial_number,
+            _ => None,
+        })
+}
+
+fn port_with_serial_number(serial_number: &str) -> Option<String> {
+    serialport::available_ports()
+        .ok()?
+        .into_iter()
+        .find(|p| {
+            matches!(&p.port_type, SerialPortType::UsbPort(info)
+                if info.serial_number.as_deref() == Some(serial_number))
+        })
+        .map(|p| p.port_name)
+}
+
+pub(super) fn default_data_bits() -> u8 {
+    8
+}
+
+pub(super) fn default_parity() -> String {
+    "none".to_string()
+}
+
+pub(super) fn default_stop_bits() -> u8 {
+    1
+}
+
+fn default_flow_control() -> String {
+    "none".to_string()
+}
+
+pub(super) fn data_bits(bits: u8) -> Result<DataBits, Error> {
+    match bits {
+        5 => Ok(DataBits::Five),
+        6 => Ok(DataBits::Six),
+        7 => Ok(DataBits::Seven),
+        8 => Ok(DataBits::Eight),
+        other => Err(Error::invalid_config(
+            "data_bits",
+            format!("expected one of 5, 6, 7, 8, got {}", other),
+        )),
+    }
+}
+
+pub(super) fn parity(parity: &str) -> Result<Parity, Error> {
+    match parity.to_lowercase().as_str() {
+        "none" => Ok(Parity::None),
+        "odd" => Ok(Parity::Odd),
+        "even" => Ok(Parity::Even),
+        _ => Err(Error::invalid_config(
+            "parity",
+            format!("expected one of none, odd, even, got \"{}\"", parity),
+        )),
+    }
+}
+
+pub(super) fn stop_bits(bits: u8) -> Result<StopBits, Error> {
+    match bits {
+        1 => Ok(StopBits::One),
+        2 => Ok(StopBits::Two),
+        other => Err(Error::invalid_config(
+            "stop_bits",
+            format!("expected one of 1, 2, got {}", other),
+        )),
+    }
+}
+
+impl SerialConfig {
+    ///
+    /// The port to open. An adapter matched by serial number is followed to
+    /// whatever name it enumerates as now, e.g. ttyACM0 coming back as ttyACM1.
+    ///
+    fn port(&self) -> String {
+        let by_serial_number = self
+            .reconnect
+            .as_ref()
+            .is_some_and(|r| r.match_by == PortMatch::SerialNumber);
+
+        if by_serial_number
+            && let Some(port) = self.serial_number.as_deref().and_then(port_with_serial_number)
+        {
+            return port;
+        }
+
+        self.name.clone()
+    }
+
+    fn data_bits(&self) -> Result<DataBits, Error> {
+        data_bits(self.data_bits)
+    }
+
+    fn parity(&self) -> Result<Parity, Error> {
+        parity(&self.parity)
+    }
+
+    fn stop_bits(&self) -> Result<StopBits, Error> {
+        stop_bits(self.stop_bits)
+    }
+
+    fn flow_control(&self) -> Result<FlowControl, Error> {
+        match self.flow_control.to_lowercase().as_str() {
+            "none" => Ok(FlowControl::None),
+            "software" => Ok(FlowControl::Software),
+            "hardware" => Ok(FlowControl::Hardware),
+            _ => Err(Error::invalid_config(
+                "flow_control",
+                format!(
+                    "expected one of none, software, hardware, got \"{}\"",
+                    self.flow_control
+                ),
+            )),
+        }
+    }
 }
 
 impl DeviceConfig for SerialConfig {
@@ -74,6 +272,18 @@ impl DeviceConfig for SerialConfig {
     fn serialize(&self) -> Value {
         serde_json::to_value(self).unwrap()
     }
+
+    fn reconnect(&self) -> Option<ReconnectPolicy> {
+        self.reconnect.clone()
+    }
+
+    fn sequence(&self, name: &str) -> Option<Vec<SequenceStep>> {
+        self.sequences.get(name).cloned()
+    }
+
+    fn on_open(&self) -> Option<Vec<SequenceStep>> {
+        self.sequence(self.on_open.as_ref()?)
+    }
 }
 
 struct SerialManager {}
@@ -83,16 +293,35 @@ impl DeviceManager for SerialManager {
         "serial"
     }
 
-    fn open(&self, config: Value) -> Result<DeviceRef, Error> {
-        let config: SerialConfig = serde_json::from_value(config).map_err(|_| {
-            Error::new(
-                ErrorKind::InvalidConfig,
-                "Internal error: the config passed to this handler is not valid",
-            )
-        })?;
+    fn connect(&self, config: Value) -> Result<Device, Error> {
+        let mut config: SerialConfig = parse_config(config)?;
 
-        let serial_port = serialport::new(config.name(), config.baud_rate)
+        if config.baud_rate == 0 {
+            return Err(Error::invalid_config("baud_rate", "must be greater than 0"));
+        }
+
+        if config.timeout == 0 {
+            return Err(Error::invalid_config("timeout", "must be greater than 0"));
+        }
+
+        if config.reconnect.as_ref().is_some_and(|r| r.interval == 0) {
+            return Err(Error::invalid_config("reconnect", "interval must be greater than 0"));
+        }
+
+        if let Some(name) = config.on_open.as_ref()
+            && config.sequence(name).is_none()
+        {
+            return Err(Error::invalid_config("on_open", format!("no sequence named {}", name)));
+        }
+
+        let port = config.port();
+
+        let serial_port = serialport::new(&port, config.baud_rate)
             .timeout(Duration::from_millis(config.timeout))
+            .data_bits(config.data_bits()?)
+            .parity(config.parity()?)
+            .stop_bits(config.stop_bits()?)
+            .flow_control(config.flow_control()?)
             .open()
             .map_err(|e| {
                 Error::new(
@@ -101,34 +330,40 @@ impl DeviceManager for SerialManager {
                 )
             })?;
 
+        if config.serial_number.is_none() {
+            config.serial_number = serial_number(&port);
+        }
+
         let channel = Box::new(SerialChannel {
             port: Mutex::new(Some(serial_port)),
         });
 
-        let device = Device::new(config.name.clone(), channel, Box::new(config));
-
-        let reference = DeviceRef::new();
-
-        device_pool!().register(device, &reference);
-
-        Ok(reference)
+        // The on_open sequence is run by the device drive, once the device
+        // is in the pool
+        Ok(Device::new(config.name.clone(), channel, Box::new(config)))
     }
 
-    fn available(&self) -> Vec<String> {
+    fn available(&self) -> Vec<PortDescriptor> {
         let ports = serialport::available_ports().unwrap_or(Vec::new());
 
-        ports.iter().map(|p| p.port_name.clone()).collect()
+        ports.into_iter().map(describe).collect()
     }
 }
 
 impl<R: tauri::Runtime> BuilderConfig<R> {
     pub fn serial(self) -> BuilderConfig<R> {
         self.register_device_manager(Box::new(SerialManager {}))
+            .register_commands(generate_handler![baud::detect_baud], &["detect_baud"])
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::SerialConfig;
+    use crate::device::sequence::SequenceStep;
+    use crate::device::{DeviceConfig, PortMatch};
+    use serde_json::json;
+    use serialport::{DataBits, FlowControl, Parity, StopBits};
     use std::fs::File;
     use std::io::Write;
     use std::thread::sleep;
@@ -164,4 +399,82 @@ mod tests {
             sleep(Duration::from_millis(100));
         }
     }
+
+    #[test]
+    fn test_line_settings() {
+        let config: SerialConfig = serde_json::from_value(json!({
+            "name": "/dev/ttyUSB0",
+            "baud_rate": 9600,
+            "timeout": 10,
+            "data_bits": 7,
+            "parity": "even",
+            "flow_control": "hardware",
+        }))
+        .unwrap();
+
+        assert_eq!(config.data_bits().unwrap(), DataBits::Seven);
+        assert_eq!(config.parity().unwrap(), Parity::Even);
+        assert_eq!(config.stop_bits().unwrap(), StopBits::One);
+        assert_eq!(config.flow_control().unwrap(), FlowControl::Hardware);
+    }
+
+    #[test]
+    fn test_invalid_line_settings() {
+        let config: SerialConfig = serde_json::from_value(json!({
+            "name": "/dev/ttyUSB0",
+            "baud_rate": 9600,
+            "timeout": 10,
+            "data_bits": 9,
+            "parity": "mark",
+            "stop_bits": 3,
+            "flow_control": "xon",
+        }))
+        .unwrap();
+
+        assert!(config.data_bits().unwrap_err().message.contains("data_bits"));
+        assert!(config.parity().unwrap_err().message.contains("parity"));
+        assert!(config.stop_bits().unwrap_err().message.contains("stop_bits"));
+        assert!(config.flow_control().unwrap_err().message.contains("flow_control"));
+    }
+
+    #[test]
+    fn test_reconnect_policy() {
+        let config: SerialConfig = serde_json::from_value(json!({
+            "name": "/dev/ttyACM0",
+            "baud_rate": 115200,
+            "timeout": 10,
+            "reconnect": {"max_attempts": 5, "match": "serial_number"},
+            "serial_number": "0671FF485550755187121526",
+        }))
+        .unwrap();
+
+        let policy = config.reconnect().unwrap();
+        assert_eq!(policy.interval, 1000);
+        assert_eq!(policy.max_attempts, Some(5));
+        assert_eq!(policy.match_by, PortMatch::SerialNumber);
+
+        // Not plugged in, so the configured port is used
+        assert_eq!(config.port(), "/dev/ttyACM0");
+
+        // The recorded serial number survives the round trip used to reconnect
+        let config: SerialConfig = serde_json::from_value(config.serialize()).unwrap();
+        assert_eq!(config.serial_number.as_deref(), Some("0671FF485550755187121526"));
+    }
+
+    #[test]
+    fn test_sequences() {
+        let config: SerialConfig = serde_json::from_value(json!({
+            "name": "/dev/ttyUSB0",
+            "baud_rate": 115200,
+            "timeout": 10,
+            "sequences": {
+                "reset": [{"rts": true}, {"wait": 100}, {"dtr": false}, {"wait": 50}, {"rts": false}],
+            },
+            "on_open": "reset",
+        }))
+        .unwrap();
+
+        assert_eq!(config.sequence("reset").unwrap()[1], SequenceStep::Wait(100));
+        assert_eq!(config.sequence("bootloader"), None);
+    }
 }
@@ -0,0 +1,303 @@
+use crate::config::BuilderConfig;
+use crate::device::{
+    parse_config, Device, DeviceChannel, DeviceConfig, DeviceManager, PortDescriptor,
+};
+use crate::err::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+///
+/// A TCP client connection, e.g. to a ser2net or ESP-link bridge.
+///
+/// The stream is non-blocking so that `available` can peek at the socket
+/// without stalling, until a reader is taken and the socket switches to
+/// blocking reads with a timeout.
+///
+struct TcpChannel {
+    stream: Option<TcpStream>,
+    split: bool,
+}
+
+struct TcpReader {
+    stream: TcpStream,
+}
+
+impl Read for TcpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.stream.read(buf) {
+            Ok(0) => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "TCP connection closed by peer.",
+            )),
+            other => other,
+        }
+    }
+}
+
+impl TcpChannel {
+    fn stream(&self) -> io::Result<&TcpStream> {
+        self.stream
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "TCP stream is closed."))
+    }
+
+    fn stream_mut(&mut self) -> io::Result<&mut TcpStream> {
+        self.stream
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "TCP stream is closed."))
+    }
+}
+
+impl Read for TcpChannel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.split {
+            return Ok(0);
+        }
+
+        self.stream_mut()?.read(buf)
+    }
+}
+
+impl Write for TcpChannel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream_mut()?.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream_mut()?.flush()
+    }
+}
+
+impl DeviceChannel for TcpChannel {
+    fn available(&self) -> io::Result<usize> {
+        if self.split {
+            return Ok(0);
+        }
+
+        let mut buf = [0u8; 4096];
+
+        match self.stream()?.peek(&mut buf) {
+            // A readable socket with nothing in it means the peer hung up
+            Ok(0) => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "TCP connection closed by peer.",
+            )),
+            Ok(len) => Ok(len),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        let stream = self.stream_mut()?;
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        let reader = TcpReader {
+            stream: stream.try_clone()?,
+        };
+        self.split = true;
+
+        Ok(Box::new(reader))
+    }
+
+    fn close(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct TcpConfig {
+    name: String,
+    host: String,
+    port: u16,
+    #[serde(default = "default_connect_timeout")]
+    connect_timeout: u64,
+    #[serde(default)]
+    nodelay: bool,
+}
+
+fn default_connect_timeout() -> u64 {
+    3000
+}
+
+impl DeviceConfig for TcpConfig {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn serialize(&self) -> Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl TcpConfig {
+    fn addresses(&self) -> Result<Vec<SocketAddr>, Error> {
+        let addresses = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|e| Error::invalid_config("host", format!("cannot resolve {}: {}", self.host, e)))?
+            .collect::<Vec<SocketAddr>>();
+
+        if addresses.is_empty() {
+            return Err(Error::invalid_config(
+                "host",
+                format!("{} did not resolve to any address", self.host),
+            ));
+        }
+
+        Ok(addresses)
+    }
+}
+
+struct TcpManager {}
+
+impl DeviceManager for TcpManager {
+    fn sort(&self) -> &'static str {
+        "tcp"
+    }
+
+    fn connect(&self, config: Value) -> Result<Device, Error> {
+        let config: TcpConfig = parse_config(config)?;
+
+        if config.connect_timeout == 0 {
+            return Err(Error::invalid_config("connect_timeout", "must be greater than 0"));
+        }
+
+        let timeout = Duration::from_millis(config.connect_timeout);
+
+        let mut last_error = None;
+        let mut stream = None;
+        for address in config.addresses()? {
+            match TcpStream::connect_timeout(&address, timeout) {
+                Ok(s) => {
+                    stream = Some(s);
+                    break;
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        let stream = stream.ok_or_else(|| {
+            Error::new(
+                ErrorKind::NetworkError,
+                format!(
+                    "Failed to connect to {}:{}. {}",
+                    config.host,
+                    config.port,
+                    last_error.map_or(String::new(), |e| e.to_string())
+                ),
+            )
+        })?;
+
+        stream.set_nodelay(config.nodelay)?;
+        stream.set_nonblocking(true)?;
+
+        let channel = Box::new(TcpChannel {
+            stream: Some(stream),
+            split: false,
+        });
+
+        Ok(Device::new(config.name.clone(), channel, Box::new(config)))
+    }
+
+    fn available(&self) -> Vec<PortDescriptor> {
+        // Remote endpoints cannot be enumerated
+        Vec::new()
+    }
+}
+
+impl<R: tauri::Runtime> BuilderConfig<R> {
+    pub fn tcp(self) -> BuilderConfig<R> {
+        self.register_device_manager(Box::new(TcpManager {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TcpChannel, TcpManager};
+    use crate::device::{DeviceChannel, DeviceManager};
+    use serde_json::json;
+    use std::io;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut device = TcpManager {}
+            .connect(json!({"name": "bridge", "host": "127.0.0.1", "port": port}))
+            .unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        device.write(b"[ping]\n").unwrap();
+        device.flush().unwrap();
+
+        let mut buf = [0u8; 7];
+        peer.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"[ping]\n");
+
+        peer.write_all(b"rpm = 1200\n").unwrap();
+        sleep(Duration::from_millis(50));
+
+        // Peeking leaves the data for the read
+        let channel = device.channel_as::<TcpChannel>().unwrap();
+        assert_eq!(channel.available().unwrap(), 11);
+        assert_eq!(channel.available().unwrap(), 11);
+        assert_eq!(device.read_available().unwrap(), b"rpm = 1200\n");
+        assert_eq!(device.read_available().unwrap(), b"");
+
+        drop(peer);
+        sleep(Duration::from_millis(50));
+
+        let channel = device.channel_as::<TcpChannel>().unwrap();
+        assert_eq!(
+            channel.available().unwrap_err().kind(),
+            io::ErrorKind::ConnectionAborted
+        );
+    }
+
+    #[test]
+    fn test_reader_sees_peer_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut device = TcpManager {}
+            .connect(json!({"name": "bridge", "host": "127.0.0.1", "port": port}))
+            .unwrap();
+        let (mut peer, _) = listener.accept().unwrap();
+
+        let mut reader = device.reader().unwrap();
+        let mut buf = [0u8; 64];
+
+        // Nothing arrived yet, the read times out
+        assert!(matches!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+        ));
+
+        peer.write_all(b"volts = 12\n").unwrap();
+        assert_eq!(reader.read(&mut buf).unwrap(), 11);
+        assert_eq!(&buf[..11], b"volts = 12\n");
+
+        // The channel itself no longer reads once the reader is taken
+        assert_eq!(device.read_available().unwrap(), b"");
+
+        drop(peer);
+        assert_eq!(
+            reader.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::ConnectionAborted
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use crate::config::BuilderConfig;
+use crate::device::{
+    parse_config, Device, DeviceChannel, DeviceConfig, DeviceManager, PortDescriptor,
+};
+use crate::err::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const MAX_DATAGRAM: usize = 65536;
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+///
+/// Only datagrams from a matching sender are kept, either an exact
+/// `ip:port` or any port on an `ip`.
+///
+#[derive(Debug, PartialEq, Clone)]
+enum SourceFilter {
+    Address(SocketAddr),
+    Ip(IpAddr),
+}
+
+impl SourceFilter {
+    fn parse(value: &str) -> Option<SourceFilter> {
+        if let Ok(address) = value.parse::<SocketAddr>() {
+            Some(SourceFilter::Address(address))
+        } else {
+            value.parse::<IpAddr>().ok().map(SourceFilter::Ip)
+        }
+    }
+
+    fn matches(&self, from: &SocketAddr) -> bool {
+        match self {
+            SourceFilter::Address(address) => address == from,
+            SourceFilter::Ip(ip) => ip == &from.ip(),
+        }
+    }
+}
+
+///
+/// A bound UDP socket. Received datagrams are drained from the (non-blocking)
+/// socket into `pending`, so `available` reports the exact number of bytes
+/// that a subsequent `read` can return. Once a reader is taken, the socket
+/// blocks with a timeout and datagrams go to the reader instead.
+///
+struct UdpChannel {
+    socket: Option<UdpSocket>,
+    source: Option<SourceFilter>,
+    peer: Option<SocketAddr>,
+    pending: Mutex<VecDeque<Vec<u8>>>,
+    split: bool,
+}
+
+struct UdpReader {
+    socket: UdpSocket,
+    source: Option<SourceFilter>,
+    datagram: Vec<u8>,
+    /// The part of the last datagram that did not fit in the caller's buffer
+    leftover: VecDeque<u8>,
+}
+
+impl Read for UdpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.leftover.is_empty() {
+            let (len, from) = self.socket.recv_from(&mut self.datagram)?;
+
+            if self.source.as_ref().is_some_and(|f| !f.matches(&from)) {
+                return Ok(0);
+            }
+
+            self.leftover.extend(&self.datagram[..len]);
+        }
+
+        let len = buf.len().min(self.leftover.len());
+        for (i, b) in self.leftover.drain(..len).enumerate() {
+            buf[i] = b;
+        }
+
+        Ok(len)
+    }
+}
+
+impl UdpChannel {
+    fn socket(&self) -> io::Result<&UdpSocket> {
+        self.socket
+            .as_ref()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "UDP socket is closed."))
+    }
+
+    fn receive_pending(&self) -> io::Result<()> {
+        let socket = self.socket()?;
+        let mut pending = self.pending.lock().unwrap();
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+
+        loop {
+            let (len, from) = match socket.recv_from(&mut buf) {
+                Ok(x) => x,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) => return Err(e),
+            };
+
+            if self.source.as_ref().is_some_and(|f| !f.matches(&from)) {
+                continue;
+            }
+
+            if len > 0 {
+                pending.push_back(buf[..len].to_vec());
+            }
+        }
+    }
+}
+
+impl Read for UdpChannel {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.split {
+            return Ok(0);
+        }
+
+        self.receive_pending()?;
+
+        let mut pending = self.pending.lock().unwrap();
+        let mut written = 0;
+
+        while written < buf.len() {
+            let Some(datagram) = pending.front_mut() else {
+                break;
+            };
+
+            let len = datagram.len().min(buf.len() - written);
+            buf[written..written + len].copy_from_slice(&datagram[..len]);
+            written += len;
+
+            if len == datagram.len() {
+                pending.pop_front();
+            } else {
+                datagram.drain(..len);
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+impl Write for UdpChannel {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let peer = self.peer.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotConnected, "No UDP peer is configured.")
+        })?;
+
+        self.socket()?.send_to(buf, peer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl DeviceChannel for UdpChannel {
+    fn available(&self) -> io::Result<usize> {
+        if self.split {
+            return Ok(0);
+        }
+
+        self.receive_pending()?;
+
+        Ok(self.pending.lock().unwrap().iter().map(|d| d.len()).sum())
+    }
+
+    fn reader(&mut self) -> io::Result<Box<dyn Read + Send>> {
+        self.receive_pending()?;
+
+        let socket = self.socket()?;
+        socket.set_nonblocking(false)?;
+        socket.set_read_timeout(Some(READ_TIMEOUT))?;
+
+        let reader = UdpReader {
+            socket: socket.try_clone()?,
+            source: self.source.clone(),
+            datagram: vec![0u8; MAX_DATAGRAM],
+            leftover: self.pending.lock().unwrap().drain(..).flatten().collect(),
+        };
+        self.split = true;
+
+        Ok(Box::new(reader))
+    }
+
+    fn close(&mut self) {
+        self.socket.take();
+        self.pending.lock().unwrap().clear();
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct UdpConfig {
+    name: String,
+    #[serde(default = "default_bind")]
+    bind: String,
+    port: u16,
+    #[serde(default)]
+    multicast: Option<String>,
+    #[serde(default)]
+    interface: Option<String>,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    peer: Option<String>,
+}
+
+fn default_bind() -> String {
+    "0.0.0.0".to_string()
+}
+
+impl DeviceConfig for UdpConfig {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn serialize(&self) -> Value {
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+impl UdpConfig {
+    fn bind_address(&self) -> Result<SocketAddr, Error> {
+        let ip = self.bind.parse::<IpAddr>().map_err(|_| {
+            Error::invalid_config("bind", format!("{} is not an IP address", self.bind))
+        })?;
+
+        Ok(SocketAddr::new(ip, self.port))
+    }
+
+    fn multicast(&self) -> Result<Option<IpAddr>, Error> {
+        let Some(group) = self.multicast.as_ref() else {
+            return Ok(None);
+        };
+
+        match group.parse::<IpAddr>() {
+            Ok(ip) if ip.is_multicast() => Ok(Some(ip)),
+            _ => Err(Error::invalid_config(
+                "multicast",
+                format!("{} is not a multicast group address", group),
+            )),
+        }
+    }
+
+    fn interface(&self) -> Result<Ipv4Addr, Error> {
+        match self.interface.as_ref() {
+            None => Ok(Ipv4Addr::UNSPECIFIED),
+            Some(interface) => interface.parse::<Ipv4Addr>().map_err(|_| {
+                Error::invalid_config(
+                    "interface",
+                    format!("{} is not an IPv4 address", interface),
+                )
+            }),
+        }
+    }
+
+    fn source(&self) -> Result<Option<SourceFilter>, Error> {
+        match self.source.as_ref() {
+            None => Ok(None),
+            Some(source) => SourceFilter::parse(source).map(Some).ok_or_else(|| {
+                Error::invalid_config(
+                    "source",
+                    format!("{} is neither an IP address nor ip:port", source),
+                )
+            }),
+        }
+    }
+
+    fn peer(&self) -> Result<Option<SocketAddr>, Error> {
+        let Some(peer) = self.peer.as_ref() else {
+            return Ok(None);
+        };
+
+        peer.to_socket_addrs()
+            .ok()
+            .and_then(|mut a| a.next())
+            .map(Some)
+            .ok_or_else(|| Error::invalid_config("peer", format!("cannot resolve {}", peer)))
+    }
+}
+
+struct UdpManager {}
+
+impl DeviceManager for UdpManager {
+    fn sort(&self) -> &'static str {
+        "udp"
+    }
+
+    fn connect(&self, config: Value) -> Result<Device, Error> {
+        let config: UdpConfig = parse_config(config)?;
+
+        let address = config.bind_address()?;
+        let multicast = config.multicast()?;
+        let interface = config.interface()?;
+        let source = config.source()?;
+        let peer = config.peer()?;
+
+        let socket = UdpSocket::bind(address).map_err(|e| {
+            Error::new(
+                ErrorKind::NetworkError,
+                format!("Failed to bind UDP socket to {}. {}", address, e),
+            )
+        })?;
+
+        match multicast {
+            Some(IpAddr::V4(group)) => socket.join_multicast_v4(&group, &interface)?,
+            Some(IpAddr::V6(group)) => socket.join_multicast_v6(&group, 0)?,
+            None => {}
+        }
+
+        socket.set_nonblocking(true)?;
+
+        let channel = Box::new(UdpChannel {
+            socket: Some(socket),
+            source,
+            peer,
+            pending: Mutex::new(VecDeque::new()),
+            split: false,
+        });
+
+        Ok(Device::new(config.name.clone(), channel, Box::new(config)))
+    }
+
+    fn available(&self) -> Vec<PortDescriptor> {
+        // Datagram sources cannot be enumerated
+        Vec::new()
+    }
+}
+
+impl<R: tauri::Runtime> BuilderConfig<R> {
+    pub fn udp(self) -> BuilderConfig<R> {
+        self.register_device_manager(Box::new(UdpManager {}))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SourceFilter, UdpChannel};
+    use crate::device::DeviceChannel;
+    use std::collections::VecDeque;
+    use std::io::Read;
+    use std::net::UdpSocket;
+    use std::sync::Mutex;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    fn channel(source: Option<SourceFilter>) -> (UdpChannel, UdpSocket) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_nonblocking(true).unwrap();
+
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        sender.connect(socket.local_addr().unwrap()).unwrap();
+
+        let channel = UdpChannel {
+            socket: Some(socket),
+            source,
+            peer: None,
+            pending: Mutex::new(VecDeque::new()),
+            split: false,
+        };
+
+        (channel, sender)
+    }
+
+    #[test]
+    fn test_available_counts_pending_datagrams() {
+        let (mut channel, sender) = channel(None);
+
+        sender.send(b"speed = 1\n").unwrap();
+        sender.send(b"speed = 2\n").unwrap();
+        sleep(Duration::from_millis(50));
+
+        assert_eq!(channel.available().unwrap(), 20);
+
+        let mut buf = [0u8; 15];
+        assert_eq!(channel.read(&mut buf).unwrap(), 15);
+        assert_eq!(&buf, b"speed = 1\nspeed");
+        assert_eq!(channel.available().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_source_filter() {
+        let (mut channel, sender) = channel(Some(SourceFilter::Ip("10.0.0.1".parse().unwrap())));
+
+        sender.send(b"ignored\n").unwrap();
+        sleep(Duration::from_millis(50));
+
+        assert_eq!(channel.available().unwrap(), 0);
+        assert_eq!(channel.read(&mut [0u8; 8]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_reader_takes_pending_datagrams() {
+        let (mut channel, sender) = channel(None);
+
+        sender.send(b"before\n").unwrap();
+        sleep(Duration::from_millis(50));
+        assert_eq!(channel.available().unwrap(), 7);
+
+        let mut reader = channel.reader().unwrap();
+        sender.send(b"after\n").unwrap();
+
+        let mut buf = [0u8; 64];
+        let len = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"before\n");
+        let len = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"after\n");
+
+        assert_eq!(channel.available().unwrap(), 0);
+    }
+}
@@ -14,6 +14,13 @@ impl Error {
             message: message.into(),
         }
     }
+
+    pub fn invalid_config<F: AsRef<str>, M: AsRef<str>>(field: F, message: M) -> Self {
+        Error::new(
+            ErrorKind::InvalidConfig,
+            format!("Invalid config field `{}`: {}", field.as_ref(), message.as_ref()),
+        )
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -21,11 +28,19 @@ pub enum ErrorKind {
     // Kind, error
     IO,
     SerialError,
+    NetworkError,
     UnknownDeviceManager,
     InvalidConfig,
     TauriError,
     AlreadyOpen,
     NoSuchProject,
+    NoSuchWorkspace,
+    NoSuchDevice,
+    NoSuchSequence,
+    InvalidCommand,
+    InvalidArgument,
+    Timeout,
+    Unsupported,
     SerdeError,
     UpdaterError,
 }
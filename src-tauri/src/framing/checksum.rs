@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumKind {
+    /// CRC-8/SMBUS
+    Crc8,
+    /// CRC-16/CCITT-FALSE
+    Crc16Ccitt,
+    /// CRC-16/XMODEM
+    Crc16Xmodem,
+    /// CRC-16/MODBUS, sent little endian on the wire
+    Crc16Modbus,
+    /// CRC-32/ISO-HDLC, as used by zlib and Ethernet
+    Crc32,
+    /// All bytes XORed together
+    Xor,
+    /// `$payload*hh`, the XOR of the payload as two hex digits
+    Nmea,
+}
+
+///
+/// The `checksum` section of a device config. Binary checksums trail the
+/// frame in the byte order of their kind unless `little_endian` says
+/// otherwise.
+///
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct ChecksumConfig {
+    #[serde(rename = "type")]
+    pub kind: ChecksumKind,
+    #[serde(default)]
+    pub little_endian: Option<bool>,
+}
+
+fn crc8(data: &[u8]) -> u32 {
+    let mut crc = 0u8;
+
+    for b in data {
+        crc ^= b;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+    }
+
+    crc as u32
+}
+
+fn crc16(data: &[u8], init: u16) -> u32 {
+    let mut crc = init;
+
+    for b in data {
+        crc ^= (*b as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x1021 } else { crc << 1 };
+        }
+    }
+
+    crc as u32
+}
+
+fn crc16_modbus(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFu16;
+
+    for b in data {
+        crc ^= *b as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xA001 } else { crc >> 1 };
+        }
+    }
+
+    crc as u32
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFFu32;
+
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+        }
+    }
+
+    !crc
+}
+
+fn xor(data: &[u8]) -> u8 {
+    data.iter().fold(0, |acc, b| acc ^ b)
+}
+
+impl ChecksumKind {
+    /// Size of the trailing checksum in bytes, for binary checksums
+    fn width(&self) -> usize {
+        match self {
+            ChecksumKind::Crc8 | ChecksumKind::Xor => 1,
+            ChecksumKind::Crc16Ccitt | ChecksumKind::Crc16Xmodem | ChecksumKind::Crc16Modbus => 2,
+            ChecksumKind::Crc32 => 4,
+            ChecksumKind::Nmea => 0,
+        }
+    }
+
+    /// Byte order on the wire, only Modbus sends the low byte first
+    fn little_endian(&self) -> bool {
+        *self == ChecksumKind::Crc16Modbus
+    }
+
+    pub fn compute(&self, data: &[u8]) -> u32 {
+        match self {
+            ChecksumKind::Crc8 => crc8(data),
+            ChecksumKind::Crc16Ccitt => crc16(data, 0xFFFF),
+            ChecksumKind::Crc16Xmodem => crc16(data, 0),
+            ChecksumKind::Crc16Modbus => crc16_modbus(data),
+            ChecksumKind::Crc32 => crc32(data),
+            ChecksumKind::Xor | ChecksumKind::Nmea => xor(data) as u32,
+        }
+    }
+}
+
+impl ChecksumConfig {
+    ///
+    /// Checks the checksum of a frame and returns the frame without it, or
+    /// `None` if it does not match or is missing.
+    ///
+    pub fn verify<'a>(&self, frame: &'a [u8]) -> Option<&'a [u8]> {
+        if self.kind == ChecksumKind::Nmea {
+            return verify_nmea(frame);
+        }
+
+        let width = self.kind.width();
+        if frame.len() < width {
+            return None;
+        }
+
+        let (payload, trailer) = frame.split_at(frame.len() - width);
+
+        let mut bytes = [0u8; 4];
+        let little_endian = self.little_endian.unwrap_or(self.kind.little_endian());
+
+        let expected = if little_endian {
+            bytes[..width].copy_from_slice(trailer);
+            u32::from_le_bytes(bytes)
+        } else {
+            bytes[4 - width..].copy_from_slice(trailer);
+            u32::from_be_bytes(bytes)
+        };
+
+        (self.kind.compute(payload) == expected).then_some(payload)
+    }
+}
+
+fn verify_nmea(frame: &[u8]) -> Option<&[u8]> {
+    // Lines usually end in \r\n
+    let frame = frame.strip_suffix(b"\r").unwrap_or(frame);
+
+    let star = frame.iter().rposition(|b| *b == b'*')?;
+    let (payload, trailer) = (&frame[..star], &frame[star + 1..]);
+
+    if trailer.len() != 2 {
+        return None;
+    }
+    let expected = u8::from_str_radix(std::str::from_utf8(trailer).ok()?, 16).ok()?;
+
+    let summed = payload
+        .strip_prefix(b"$")
+        .or_else(|| payload.strip_prefix(b"!"))
+        .unwrap_or(payload);
+
+    (xor(summed) == expected).then_some(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChecksumConfig, ChecksumKind};
+
+    #[test]
+    fn test_check_values() {
+        let check = b"123456789";
+
+        assert_eq!(ChecksumKind::Crc8.compute(check), 0xF4);
+        assert_eq!(ChecksumKind::Crc16Ccitt.compute(check), 0x29B1);
+        assert_eq!(ChecksumKind::Crc16Xmodem.compute(check), 0x31C3);
+        assert_eq!(ChecksumKind::Crc16Modbus.compute(check), 0x4B37);
+        assert_eq!(ChecksumKind::Crc32.compute(check), 0xCBF43926);
+    }
+
+    #[test]
+    fn test_verify() {
+        let crc16 = ChecksumConfig {
+            kind: ChecksumKind::Crc16Ccitt,
+            little_endian: None,
+        };
+        assert_eq!(crc16.verify(b"123456789\x29\xB1"), Some(&b"123456789"[..]));
+        assert_eq!(crc16.verify(b"123456780\x29\xB1"), None);
+        assert_eq!(crc16.verify(b"1"), None);
+
+        let crc32 = ChecksumConfig {
+            kind: ChecksumKind::Crc32,
+            little_endian: Some(true),
+        };
+        assert_eq!(crc32.verify(b"123456789\x26\x39\xF4\xCB"), Some(&b"123456789"[..]));
+
+        let nmea = ChecksumConfig {
+            kind: ChecksumKind::Nmea,
+            little_endian: None,
+        };
+        let sentence = b"$GPGLL,5057.970,N,00146.110,E,142451,A*27\r";
+        assert_eq!(nmea.verify(sentence), Some(&sentence[..sentence.len() - 4]));
+        assert_eq!(nmea.verify(b"$GPGLL,5057.971,N,00146.110,E,142451,A*27"), None);
+        assert_eq!(nmea.verify(b"speed = 1"), None);
+    }
+
+    #[test]
+    fn test_verify_modbus() {
+        let modbus: ChecksumConfig =
+            serde_json::from_value(serde_json::json!({"type": "crc16_modbus"})).unwrap();
+
+        // Read 10 holding registers from unit 1, CRC 0xCDC5 sent low byte first
+        let request = b"\x01\x03\x00\x00\x00\x0A\xC5\xCD";
+        assert_eq!(modbus.verify(request), Some(&request[..6]));
+        assert_eq!(modbus.verify(b"\x01\x03\x00\x00\x00\x0A\xCD\xC5"), None);
+
+        let big_endian = ChecksumConfig {
+            little_endian: Some(false),
+            ..modbus
+        };
+        assert_eq!(big_endian.verify(b"\x01\x03\x00\x00\x00\x0A\xCD\xC5"), Some(&request[..6]));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::framing::Framer;
+
+///
+/// Consistent Overhead Byte Stuffing, frames are delimited by `0x00`.
+///
+pub struct CobsFramer {
+    max_length: usize,
+    buffer: Vec<u8>,
+    /// Set while skipping the rest of an overlong frame
+    discarding: bool,
+}
+
+impl CobsFramer {
+    pub fn new(max_length: usize) -> CobsFramer {
+        CobsFramer {
+            max_length,
+            buffer: Vec::new(),
+            discarding: false,
+        }
+    }
+}
+
+pub fn decode(encoded: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+
+    while i < encoded.len() {
+        let code = encoded[i] as usize;
+        if code == 0 {
+            return None;
+        }
+
+        let end = i + code;
+        if end > encoded.len() {
+            return None;
+        }
+
+        decoded.extend_from_slice(&encoded[i + 1..end]);
+        i = end;
+
+        // A full block of 254 bytes is not followed by an implicit zero
+        if code < 0xFF && i < encoded.len() {
+            decoded.push(0);
+        }
+    }
+
+    Some(decoded)
+}
+
+impl Framer for CobsFramer {
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+
+        for b in data {
+            if *b != 0 {
+                if self.buffer.len() < self.max_length {
+                    self.buffer.push(*b);
+                } else {
+                    self.buffer.clear();
+                    self.discarding = true;
+                }
+                continue;
+            }
+
+            let encoded = std::mem::take(&mut self.buffer);
+
+            if !std::mem::take(&mut self.discarding)
+                && !encoded.is_empty()
+                && let Some(frame) = decode(&encoded)
+            {
+                frames.push(frame);
+            }
+        }
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CobsFramer;
+    use crate::framing::Framer;
+
+    #[test]
+    fn test_decode_frames() {
+        let mut framer = CobsFramer::new(512);
+
+        // [0x11, 0x22, 0x00, 0x33] and [0x00]
+        assert_eq!(
+            framer.push(&[0x03, 0x11, 0x22, 0x02, 0x33, 0x00, 0x01, 0x01]),
+            vec![vec![0x11, 0x22, 0x00, 0x33]]
+        );
+        assert_eq!(framer.push(&[0x00]), vec![vec![0x00]]);
+
+        // A code running past the delimiter is malformed and dropped
+        assert_eq!(framer.push(&[0x05, 0x11, 0x00]), Vec::<Vec<u8>>::new());
+
+        let mut block = vec![0xFF];
+        block.extend(1..=254u8);
+        block.push(0x00);
+        assert_eq!(framer.push(&block), vec![(1..=254u8).collect::<Vec<u8>>()]);
+    }
+}
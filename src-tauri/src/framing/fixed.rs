@@ -0,0 +1,48 @@
+use crate::framing::Framer;
+
+///
+/// Every frame is exactly `size` bytes
+///
+pub struct FixedFramer {
+    size: usize,
+    buffer: Vec<u8>,
+}
+
+impl FixedFramer {
+    pub fn new(size: usize) -> FixedFramer {
+        FixedFramer {
+            size,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Framer for FixedFramer {
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        let complete = self.buffer.len() / self.size * self.size;
+
+        self.buffer
+            .drain(..complete)
+            .collect::<Vec<u8>>()
+            .chunks(self.size)
+            .map(|c| c.to_vec())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FixedFramer;
+    use crate::framing::Framer;
+
+    #[test]
+    fn test_fixed_frames() {
+        let mut framer = FixedFramer::new(3);
+
+        assert_eq!(framer.push(&[1, 2]), Vec::<Vec<u8>>::new());
+        assert_eq!(framer.push(&[3, 4, 5, 6, 7]), vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        assert_eq!(framer.push(&[8, 9]), vec![vec![7, 8, 9]]);
+    }
+}
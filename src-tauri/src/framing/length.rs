@@ -0,0 +1,87 @@
+use crate::framing::Framer;
+
+///
+/// Each frame is preceded by its payload length as a 1, 2 or 4 byte
+/// integer. A length over `max_length` means we are out of step, so one byte
+/// is skipped until a plausible length turns up.
+///
+pub struct LengthPrefixedFramer {
+    width: usize,
+    big_endian: bool,
+    max_length: usize,
+    buffer: Vec<u8>,
+}
+
+impl LengthPrefixedFramer {
+    pub fn new(width: usize, big_endian: bool, max_length: usize) -> LengthPrefixedFramer {
+        LengthPrefixedFramer {
+            width,
+            big_endian,
+            max_length,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn length(&self) -> usize {
+        let prefix = &self.buffer[..self.width];
+
+        let mut bytes = [0u8; 4];
+        if self.big_endian {
+            bytes[4 - self.width..].copy_from_slice(prefix);
+            u32::from_be_bytes(bytes) as usize
+        } else {
+            bytes[..self.width].copy_from_slice(prefix);
+            u32::from_le_bytes(bytes) as usize
+        }
+    }
+}
+
+impl Framer for LengthPrefixedFramer {
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut frames = Vec::new();
+
+        while self.buffer.len() >= self.width {
+            let length = self.length();
+
+            if length > self.max_length {
+                self.buffer.remove(0);
+                continue;
+            }
+
+            if self.buffer.len() < self.width + length {
+                break;
+            }
+
+            frames.push(self.buffer[self.width..self.width + length].to_vec());
+            self.buffer.drain(..self.width + length);
+        }
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LengthPrefixedFramer;
+    use crate::framing::Framer;
+
+    #[test]
+    fn test_prefixes() {
+        let mut framer = LengthPrefixedFramer::new(2, true, 16);
+
+        assert_eq!(framer.push(&[0, 3, b'a']), Vec::<Vec<u8>>::new());
+        assert_eq!(
+            framer.push(&[b'b', b'c', 0, 1, b'd']),
+            vec![b"abc".to_vec(), b"d".to_vec()]
+        );
+
+        let mut framer = LengthPrefixedFramer::new(2, false, 16);
+        assert_eq!(framer.push(&[2, 0, b'x', b'y']), vec![b"xy".to_vec()]);
+
+        // 0xFF00 is too long, resynchronises on the next byte
+        let mut framer = LengthPrefixedFramer::new(2, true, 16);
+        assert_eq!(framer.push(&[0xFF, 0, 1, b'z']), vec![b"z".to_vec()]);
+    }
+}
@@ -0,0 +1,174 @@
+mod checksum;
+mod cobs;
+mod fixed;
+mod length;
+mod newline;
+mod slip;
+
+use crate::err::Error;
+use serde::{Deserialize, Serialize};
+
+pub use checksum::{ChecksumConfig, ChecksumKind};
+pub use cobs::CobsFramer;
+pub use fixed::FixedFramer;
+pub use length::LengthPrefixedFramer;
+pub use newline::NewlineFramer;
+pub use slip::SlipFramer;
+
+/// Upper bound on a frame unless the config says otherwise
+pub const DEFAULT_MAX_LENGTH: usize = 64 * 1024;
+
+///
+/// Cuts a received byte stream into whole frames, so nothing downstream has
+/// to deal with data split across reads.
+///
+pub trait Framer: Send {
+    ///
+    /// Feeds received bytes and returns the payload of every frame they
+    /// complete, without delimiters, escapes or length prefixes. Malformed
+    /// frames are dropped.
+    ///
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>>;
+}
+
+///
+/// No framing, every read is passed on as it is
+///
+pub struct RawFramer {}
+
+impl Framer for RawFramer {
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        if data.is_empty() {
+            vec![]
+        } else {
+            vec![data.to_vec()]
+        }
+    }
+}
+
+///
+/// The `framing` section of a device config
+///
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FramingConfig {
+    Raw,
+    Newline {
+        #[serde(default = "default_terminator")]
+        terminator: String,
+        #[serde(default = "default_max_length")]
+        max_length: usize,
+    },
+    LengthPrefixed {
+        /// Prefix size in bytes, 1, 2 or 4
+        #[serde(default = "default_width")]
+        width: usize,
+        #[serde(default = "default_big_endian")]
+        big_endian: bool,
+        #[serde(default = "default_max_length")]
+        max_length: usize,
+    },
+    Cobs {
+        #[serde(default = "default_max_length")]
+        max_length: usize,
+    },
+    Slip {
+        #[serde(default = "default_max_length")]
+        max_length: usize,
+    },
+    Fixed {
+        size: usize,
+    },
+}
+
+fn default_terminator() -> String {
+    "\n".to_string()
+}
+
+fn default_max_length() -> usize {
+    DEFAULT_MAX_LENGTH
+}
+
+fn default_width() -> usize {
+    2
+}
+
+fn default_big_endian() -> bool {
+    true
+}
+
+impl Default for FramingConfig {
+    /// Lines, which is what the firmware we talk to sends
+    fn default() -> Self {
+        FramingConfig::Newline {
+            terminator: default_terminator(),
+            max_length: default_max_length(),
+        }
+    }
+}
+
+impl FramingConfig {
+    pub fn build(&self) -> Result<Box<dyn Framer>, Error> {
+        let max_length = |max_length: usize| {
+            if max_length == 0 {
+                Err(Error::invalid_config("framing.max_length", "must be greater than 0"))
+            } else {
+                Ok(max_length)
+            }
+        };
+
+        Ok(match self {
+            FramingConfig::Raw => Box::new(RawFramer {}),
+            FramingConfig::Newline { terminator, max_length: max } => {
+                if terminator.is_empty() {
+                    return Err(Error::invalid_config("framing.terminator", "must not be empty"));
+                }
+
+                Box::new(NewlineFramer::new(terminator.as_bytes(), max_length(*max)?))
+            }
+            FramingConfig::LengthPrefixed { width, big_endian, max_length: max } => {
+                if ![1, 2, 4].contains(width) {
+                    return Err(Error::invalid_config(
+                        "framing.width",
+                        format!("expected one of 1, 2, 4, got {}", width),
+                    ));
+                }
+
+                Box::new(LengthPrefixedFramer::new(*width, *big_endian, max_length(*max)?))
+            }
+            FramingConfig::Cobs { max_length: max } => Box::new(CobsFramer::new(max_length(*max)?)),
+            FramingConfig::Slip { max_length: max } => Box::new(SlipFramer::new(max_length(*max)?)),
+            FramingConfig::Fixed { size } => {
+                if *size == 0 {
+                    return Err(Error::invalid_config("framing.size", "must be greater than 0"));
+                }
+
+                Box::new(FixedFramer::new(*size))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FramingConfig;
+    use serde_json::json;
+
+    #[test]
+    fn test_config() {
+        let config: FramingConfig = serde_json::from_value(json!({
+            "type": "length_prefixed",
+            "width": 1,
+        }))
+        .unwrap();
+
+        let mut framer = config.build().unwrap();
+        assert_eq!(framer.push(&[2, 0xAB, 0xCD, 1]), vec![vec![0xAB, 0xCD]]);
+
+        let mut framer = FramingConfig::default().build().unwrap();
+        assert_eq!(framer.push(b"a\nb"), vec![b"a".to_vec()]);
+
+        let bad: FramingConfig = serde_json::from_value(json!({"type": "fixed", "size": 0})).unwrap();
+        assert!(bad.build().is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use crate::framing::Framer;
+
+///
+/// Frames end with a terminator, `\n` by default. A line longer than
+/// `max_length` is passed on as it is rather than buffered forever.
+///
+pub struct NewlineFramer {
+    terminator: Vec<u8>,
+    max_length: usize,
+    buffer: Vec<u8>,
+    /// How far the buffer has been searched for a terminator
+    scanned: usize,
+}
+
+impl NewlineFramer {
+    pub fn new(terminator: &[u8], max_length: usize) -> NewlineFramer {
+        NewlineFramer {
+            terminator: terminator.to_vec(),
+            max_length,
+            buffer: Vec::new(),
+            scanned: 0,
+        }
+    }
+
+    fn find(&self, start: usize) -> Option<usize> {
+        // A terminator may straddle the previous end of the buffer
+        let from = self
+            .scanned
+            .saturating_sub(self.terminator.len() - 1)
+            .max(start);
+
+        self.buffer[from..]
+            .windows(self.terminator.len())
+            .position(|w| w == self.terminator.as_slice())
+            .map(|i| from + i)
+    }
+}
+
+impl Framer for NewlineFramer {
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+
+        let mut frames = Vec::new();
+        // Frames are cut from here on and dropped from the buffer in one go
+        let mut start = 0;
+
+        while let Some(end) = self.find(start) {
+            frames.push(self.buffer[start..end].to_vec());
+
+            start = end + self.terminator.len();
+            self.scanned = start;
+        }
+
+        if self.buffer.len() - start > self.max_length {
+            frames.push(self.buffer[start..].to_vec());
+            start = self.buffer.len();
+        }
+
+        self.buffer.drain(..start);
+        self.scanned = self.buffer.len();
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NewlineFramer;
+    use crate::framing::Framer;
+
+    #[test]
+    fn test_split_lines() {
+        let mut framer = NewlineFramer::new(b"\r\n", 16);
+
+        assert_eq!(framer.push(b"speed = 1\r"), Vec::<Vec<u8>>::new());
+        assert_eq!(
+            framer.push(b"\nspeed = 2\r\n\r\nsp"),
+            vec![b"speed = 1".to_vec(), b"speed = 2".to_vec(), b"".to_vec()]
+        );
+        assert_eq!(framer.push(b"eed = 3\r\n"), vec![b"speed = 3".to_vec()]);
+
+        // No terminator in sight
+        assert_eq!(framer.push(b"0123456789abcdefg"), vec![b"0123456789abcdefg".to_vec()]);
+    }
+
+    #[test]
+    fn test_many_lines_in_one_read() {
+        let mut framer = NewlineFramer::new(b"\n", 16);
+
+        let data = "x = 1\n".repeat(10_000) + "x = ";
+        assert_eq!(framer.push(data.as_bytes()).len(), 10_000);
+        assert_eq!(framer.push(b"2\n"), vec![b"x = 2".to_vec()]);
+    }
+}
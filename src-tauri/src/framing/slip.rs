@@ -0,0 +1,97 @@
+use crate::framing::Framer;
+
+const END: u8 = 0xC0;
+const ESC: u8 = 0xDB;
+const ESC_END: u8 = 0xDC;
+const ESC_ESC: u8 = 0xDD;
+
+///
+/// Serial Line IP framing (RFC 1055), frames end with `0xC0` and the
+/// delimiter and escape bytes are escaped inside a frame.
+///
+pub struct SlipFramer {
+    max_length: usize,
+    buffer: Vec<u8>,
+    escaped: bool,
+    /// Set when the current frame had a bad escape or grew too long
+    broken: bool,
+}
+
+impl SlipFramer {
+    pub fn new(max_length: usize) -> SlipFramer {
+        SlipFramer {
+            max_length,
+            buffer: Vec::new(),
+            escaped: false,
+            broken: false,
+        }
+    }
+}
+
+impl Framer for SlipFramer {
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+
+        for b in data {
+            let b = *b;
+
+            if b == END {
+                let frame = std::mem::take(&mut self.buffer);
+
+                if !std::mem::take(&mut self.broken) && !self.escaped && !frame.is_empty() {
+                    frames.push(frame);
+                }
+
+                self.escaped = false;
+                continue;
+            }
+
+            let decoded = if std::mem::take(&mut self.escaped) {
+                match b {
+                    ESC_END => END,
+                    ESC_ESC => ESC,
+                    _ => {
+                        self.broken = true;
+                        continue;
+                    }
+                }
+            } else if b == ESC {
+                self.escaped = true;
+                continue;
+            } else {
+                b
+            };
+
+            if self.buffer.len() < self.max_length {
+                self.buffer.push(decoded);
+            } else {
+                self.broken = true;
+            }
+        }
+
+        frames
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SlipFramer;
+    use crate::framing::Framer;
+
+    #[test]
+    fn test_unescape_frames() {
+        let mut framer = SlipFramer::new(512);
+
+        assert_eq!(
+            framer.push(&[0xC0, 0x01, 0xDB, 0xDC, 0x02]),
+            Vec::<Vec<u8>>::new()
+        );
+        assert_eq!(
+            framer.push(&[0xDB, 0xDD, 0xC0, 0x03, 0xC0]),
+            vec![vec![0x01, 0xC0, 0x02, 0xDB], vec![0x03]]
+        );
+
+        // Invalid escape
+        assert_eq!(framer.push(&[0x04, 0xDB, 0x05, 0xC0]), Vec::<Vec<u8>>::new());
+    }
+}
@@ -10,9 +10,16 @@ mod workspace;
 pub mod command;
 pub mod device;
 pub mod any;
+pub mod capture;
 pub mod drive;
 pub mod err;
+pub mod framing;
+pub mod logs;
 pub mod project;
+pub mod readout;
+mod recording;
+mod telemetry;
+pub mod timestamp;
 mod update;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -25,16 +32,24 @@ pub fn run() {
         .join(".serialdurbugger");
 
     let workspace_path = home.join("workspace");
+    let recording_path = home.join("recordings");
 
     BuilderConfig::<Wry>::new()
         .update_handler()
         .commands()
         .device()
         .serial()
+        .tcp()
+        .udp()
+        .pty()
+        .replay()
         .mock()
         .drive(50)
+        .hotplug()
         .workspace(workspace_path) // Poll every 50 ms
         .project()
+        .recorder(recording_path)
+        .telemetry(3_600_000, 512 << 20) // An hour at 1 kHz per component, 512 MiB in all
         .build()
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
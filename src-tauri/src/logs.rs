@@ -0,0 +1,267 @@
+use crate::err::Error;
+use crate::framing::{DEFAULT_MAX_LENGTH, Framer, NewlineFramer};
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+/// Colour codes, which ESP-IDF and Zephyr consoles add by default
+static ANSI: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+
+/// `[LEVEL Time: t File: f Line: n] msg`, with time in milliseconds
+const BUILTIN: &str = r"\[(?P<level>\w+)\s+Time:\s*(?P<time>\d+)\s+File:\s*(?P<file>\S+)\s+Line:\s*(?P<line>[\d.\s]+?)\s*\]\s*(?P<message>.*)";
+
+/// `0.001234 INFO message └─ module @ file:line`, as printed by defmt-print,
+/// with time in seconds
+const DEFMT: &str = r"^(?:(?P<time>\d+(?:\.\d+)?)\s+)?(?P<level>TRACE|DEBUG|INFO|WARN|ERROR)\s+(?P<message>.*?)(?:\s+└─\s+(?P<module>\S+)\s+@\s+(?P<file>[^:\s]+):(?P<line>\d+))?\s*$";
+
+/// `[00:00:01.234,000] <inf> module: message`
+const ZEPHYR: &str = r"^\[(?P<time>\d+:\d{2}:\d{2}\.\d{3},\d{3})\]\s+<(?P<level>\w+)>\s+(?:(?P<module>[\w.]+):\s+)?(?P<message>.*?)\s*$";
+
+/// `I (1234) tag: message`, with time in milliseconds
+const ESP_IDF: &str = r"^(?P<level>[EWIDV]) \((?P<time>\d+)\) (?P<module>[^:]+): (?P<message>.*?)\s*$";
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    ///
+    /// Reads the way firmware spells levels, e.g. `E`, `err`, `WARNING` or
+    /// `verbose`. Anything else counts as info.
+    ///
+    pub fn normalise(level: &str) -> LogLevel {
+        match level.trim().to_ascii_lowercase().as_str() {
+            "e" | "err" | "error" | "fatal" | "crit" | "critical" => LogLevel::Error,
+            "w" | "wrn" | "warn" | "warning" => LogLevel::Warn,
+            "d" | "dbg" | "debug" => LogLevel::Debug,
+            "t" | "v" | "trace" | "verbose" => LogLevel::Trace,
+            _ => LogLevel::Info,
+        }
+    }
+}
+
+///
+/// A line of device logging, whatever format the firmware wrote it in
+///
+#[derive(Serialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LogEntry {
+    pub level: LogLevel,
+    /// Device time in milliseconds, usually since it booted
+    pub timestamp: Option<f64>,
+    pub file: Option<String>,
+    pub line: Option<String>,
+    /// The module or tag that logged it
+    pub module: Option<String>,
+    pub message: String,
+    pub original_line: String,
+}
+
+///
+/// The `logs` section of a device config
+///
+#[derive(Deserialize, Serialize, Clone, PartialEq, Default, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Nothing is parsed
+    None,
+    /// `[LEVEL Time: t File: f Line: n] msg`
+    #[default]
+    Builtin,
+    Defmt,
+    Zephyr,
+    EspIdf,
+    /// A regular expression with a `message` capture and optionally
+    /// `level`, `time`, `file`, `line` and `module` captures
+    Custom {
+        pattern: String,
+        /// Multiplies `time` into milliseconds
+        #[serde(default = "default_time_scale")]
+        time_scale: f64,
+    },
+}
+
+fn default_time_scale() -> f64 {
+    1.0
+}
+
+enum Time {
+    Scaled(f64),
+    /// `hh:mm:ss.mmm,uuu`
+    Clock,
+}
+
+impl Time {
+    fn parse(&self, time: &str) -> Option<f64> {
+        match self {
+            Time::Scaled(scale) => time.trim().parse::<f64>().ok().map(|t| t * scale),
+            Time::Clock => {
+                let (clock, micros) = time.split_once(',')?;
+                let mut parts = clock.split(':');
+                let hours = parts.next()?.parse::<f64>().ok()?;
+                let minutes = parts.next()?.parse::<f64>().ok()?;
+                let seconds = parts.next()?.parse::<f64>().ok()?;
+
+                Some(
+                    ((hours * 60.0 + minutes) * 60.0 + seconds) * 1000.0
+                        + micros.parse::<f64>().ok()? / 1000.0,
+                )
+            }
+        }
+    }
+}
+
+///
+/// Finds log lines in received frames. Unframed data is cut into lines
+/// first, like readouts.
+///
+pub struct LogParser {
+    pattern: Option<(Regex, Time)>,
+    lines: Option<NewlineFramer>,
+}
+
+impl LogFormat {
+    /// `framed` is false if frames are arbitrary chunks of the stream
+    pub fn build(&self, framed: bool) -> Result<LogParser, Error> {
+        let (pattern, time) = match self {
+            LogFormat::None => {
+                return Ok(LogParser {
+                    pattern: None,
+                    lines: None,
+                });
+            }
+            LogFormat::Builtin => (BUILTIN, Time::Scaled(1.0)),
+            LogFormat::Defmt => (DEFMT, Time::Scaled(1000.0)),
+            LogFormat::Zephyr => (ZEPHYR, Time::Clock),
+            LogFormat::EspIdf => (ESP_IDF, Time::Scaled(1.0)),
+            LogFormat::Custom { pattern, time_scale } => {
+                (pattern.as_str(), Time::Scaled(*time_scale))
+            }
+        };
+
+        let pattern = Regex::new(pattern)
+            .map_err(|e| Error::invalid_config("logs.pattern", e.to_string()))?;
+
+        if !pattern.capture_names().any(|n| n == Some("message")) {
+            return Err(Error::invalid_config(
+                "logs.pattern",
+                "must have a `message` capture",
+            ));
+        }
+
+        Ok(LogParser {
+            pattern: Some((pattern, time)),
+            lines: (!framed).then(|| NewlineFramer::new(b"\n", DEFAULT_MAX_LENGTH)),
+        })
+    }
+}
+
+impl LogParser {
+    pub fn push(&mut self, frame: &[u8]) -> Vec<LogEntry> {
+        if self.pattern.is_none() {
+            return vec![];
+        }
+
+        let lines = match self.lines.as_mut() {
+            Some(lines) => lines.push(frame),
+            None => vec![frame.to_vec()],
+        };
+
+        lines
+            .iter()
+            .filter_map(|line| self.parse(&String::from_utf8_lossy(line)))
+            .collect()
+    }
+
+    fn parse(&self, line: &str) -> Option<LogEntry> {
+        let (pattern, time) = self.pattern.as_ref()?;
+
+        let line = ANSI.replace_all(line, "");
+        let line = line.trim_end_matches('\r');
+        let captures = pattern.captures(line)?;
+
+        let text = |c: &Captures, name: &str| {
+            c.name(name)
+                .map(|m| m.as_str().trim().to_string())
+                .filter(|m| !m.is_empty())
+        };
+
+        Some(LogEntry {
+            level: text(&captures, "level").map_or(LogLevel::Info, |l| LogLevel::normalise(&l)),
+            timestamp: text(&captures, "time").and_then(|t| time.parse(&t)),
+            file: text(&captures, "file"),
+            // Some firmware pads line numbers, e.g. ` 9. 9`
+            line: text(&captures, "line").map(|l| l.split_whitespace().collect()),
+            module: text(&captures, "module"),
+            message: text(&captures, "message").unwrap_or_default(),
+            original_line: line.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogFormat, LogLevel};
+    use serde_json::json;
+
+    #[test]
+    fn test_formats() {
+        let mut parser = LogFormat::Builtin.build(false).unwrap();
+        assert!(parser.push(b"[WARN Time: 1500 File: main.c Li").is_empty());
+
+        let entries = parser.push(b"ne: 9. 9] Low voltage\nnoise\n");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].level, LogLevel::Warn);
+        assert_eq!(entries[0].timestamp, Some(1500.0));
+        assert_eq!(entries[0].file.as_deref(), Some("main.c"));
+        assert_eq!(entries[0].line.as_deref(), Some("9.9"));
+        assert_eq!(entries[0].message, "Low voltage");
+
+        let mut parser = LogFormat::Defmt.build(true).unwrap();
+        let entry = parser.push("0.001500 ERROR Overcurrent └─ app::motor @ src/motor.rs:42".as_bytes());
+        assert_eq!(entry[0].level, LogLevel::Error);
+        assert_eq!(entry[0].timestamp, Some(1.5));
+        assert_eq!(entry[0].module.as_deref(), Some("app::motor"));
+        assert_eq!(entry[0].line.as_deref(), Some("42"));
+        assert_eq!(entry[0].message, "Overcurrent");
+
+        let mut parser = LogFormat::Zephyr.build(true).unwrap();
+        let entry = parser.push(b"\x1b[1;31m[00:01:02.345,500] <err> can: Bus off\x1b[0m\r");
+        assert_eq!(entry[0].level, LogLevel::Error);
+        assert_eq!(entry[0].timestamp, Some(62345.5));
+        assert_eq!(entry[0].module.as_deref(), Some("can"));
+        assert_eq!(entry[0].message, "Bus off");
+
+        let mut parser = LogFormat::EspIdf.build(true).unwrap();
+        let entry = parser.push(b"V (1234) wifi: Connected");
+        assert_eq!(entry[0].level, LogLevel::Trace);
+        assert_eq!(entry[0].timestamp, Some(1234.0));
+        assert_eq!(entry[0].module.as_deref(), Some("wifi"));
+    }
+
+    #[test]
+    fn test_custom() {
+        let format: LogFormat = serde_json::from_value(json!({
+            "type": "custom",
+            "pattern": r"^(?P<time>\d+)us (?P<level>\w+): (?P<message>.*)$",
+            "time_scale": 0.001,
+        }))
+        .unwrap();
+
+        let mut parser = format.build(true).unwrap();
+        let entry = parser.push(b"2500us Notice: Started");
+        assert_eq!(entry[0].level, LogLevel::Info);
+        assert_eq!(entry[0].timestamp, Some(2.5));
+        assert_eq!(entry[0].file, None);
+
+        let missing: LogFormat =
+            serde_json::from_value(json!({"type": "custom", "pattern": r"(?P<level>\w+)"})).unwrap();
+        assert!(missing.build(true).is_err());
+    }
+}
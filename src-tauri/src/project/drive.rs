@@ -1,21 +1,141 @@
-use crate::command::{Command, CommandParser};
-use crate::device::{DeviceManagers, DeviceRef};
+use crate::capture::Direction;
+use crate::command::describe::{help_schema, DeviceDescription};
+use crate::command::reply::Replies;
+use crate::command::schema::{CommandSchemas, TypedCommand};
+use crate::command::{
+    Command, CommandHandlers, CommandParser, CommandSyntax, HandlerContext, ParseError,
+};
+use crate::device::{parse_config, run_sequence, ControlLines, Device, DeviceManager, DeviceManagers, DeviceRef, ReconnectPolicy};
 use crate::drive::{Drive, Vehicle};
 use crate::err::{Error, ErrorKind};
-use serde::Serialize;
+use crate::framing::{ChecksumConfig, Framer, FramingConfig};
+use serde::{Deserialize, Serialize};
+use crate::project::write_device;
+use crate::logs::{LogEntry, LogFormat, LogParser};
+use crate::readout::{ReadoutFormat, ReadoutParser};
+use crate::recording::Recorder;
+use crate::telemetry::{DeviceTelemetry, Telemetry};
+use crate::timestamp::Timestamp;
 use serde_json::Value;
+use std::io;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 use tauri::ipc::Channel;
 use tauri::{command, State};
 
+///
+/// Received data is stamped when it is read. Frames, and the commands in
+/// them, carry the time of the read that completed the frame.
+///
 #[derive(Serialize)]
 #[serde(tag = "type", content = "data")]
 pub enum DeviceEvent {
-    RecRaw(Vec<u8>),
-    RecCommand(Command),
+    RecRaw { content: Vec<u8>, time: Timestamp },
+    /// A whole frame cut out of the received data by the device's framer,
+    /// without its checksum. Frames failing their checksum are passed on with
+    /// `valid` unset and are not parsed for commands.
+    RecFrame { content: Vec<u8>, time: Timestamp, valid: bool },
+    /// `typed` is set if the command is defined by the workspace, commands
+    /// that do not match their definition are sent as parse errors
+    RecCommand { command: Command, typed: Option<TypedCommand>, time: Timestamp },
+    /// Something that started like a command but could not be parsed
+    ParseError { error: ParseError, time: Timestamp },
+    /// A `component = value` sample, or whatever the device's readout
+    /// format says, found in a valid frame
+    Readout { component: String, value: f64, ts: Timestamp },
+    /// A log line in the device's log format, found in a valid frame
+    Log { entry: LogEntry, time: Timestamp },
+    /// The device described its commands and readouts with `set_schema`
+    Description(DeviceDescription),
+    /// Where other programs reach the device, e.g. the slave side of a
+    /// pseudo-terminal. Sent when reading starts.
+    Address(String),
+    /// The device dropped out and is being reopened, starting at attempt 1
+    Reconnecting { attempt: u32 },
+    Reconnected,
+    /// The modem input lines changed, also sent once when reading starts
+    ControlLines(ControlLines),
+    /// Running frame counts, sent when they change if the device checks
+    /// checksums
+    FrameStats(FrameStats),
+    /// Something failed that the device keeps running through, e.g. a
+    /// recording could not be written
+    Error { message: String, time: Timestamp },
     Close {error: bool},
 }
 
+#[derive(Serialize, Clone, Copy, PartialEq, Default, Debug)]
+pub struct FrameStats {
+    frames: u64,
+    invalid: u64,
+}
+
+///
+/// Per-device frame counts, kept across reconnects
+///
+#[derive(Default)]
+struct FrameCounters {
+    frames: AtomicU64,
+    invalid: AtomicU64,
+}
+
+impl FrameCounters {
+    fn count(&self, valid: bool) {
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        if !valid {
+            self.invalid.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn stats(&self) -> FrameStats {
+        FrameStats {
+            frames: self.frames.load(Ordering::Relaxed),
+            invalid: self.invalid.load(Ordering::Relaxed),
+        }
+    }
+}
+
+///
+/// How received data is cut into frames and checked, taken from the
+/// `framing` and `checksum` sections of any device config
+///
+#[derive(Deserialize)]
+struct ReceiveConfig {
+    #[serde(default)]
+    framing: FramingConfig,
+    #[serde(default)]
+    checksum: Option<ChecksumConfig>,
+    #[serde(default)]
+    commands: CommandSyntax,
+    #[serde(default)]
+    readouts: ReadoutFormat,
+    #[serde(default)]
+    logs: LogFormat,
+    /// Ask the device to describe itself once a project attaches
+    #[serde(default)]
+    describe: bool,
+}
+
+impl ReceiveConfig {
+    fn readout_parser(&self) -> Result<ReadoutParser, Error> {
+        self.readouts.build(self.framed())
+    }
+
+    fn log_parser(&self) -> Result<LogParser, Error> {
+        self.logs.build(self.framed())
+    }
+
+    /// Raw reads can split a line, anything else keeps it whole
+    fn framed(&self) -> bool {
+        !matches!(self.framing, FramingConfig::Raw)
+    }
+}
+
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub fn open_device(
     sort: String,
     name: String,
@@ -25,6 +145,11 @@ pub fn open_device(
 
     managers: State<DeviceManagers>,
     driver: State<Vehicle>,
+    recorder: State<Arc<Recorder>>,
+    telemetry: State<Arc<Telemetry>>,
+    schemas: State<Arc<CommandSchemas>>,
+    replies: State<Arc<Replies>>,
+    handlers: State<CommandHandlers>,
 ) -> Result<DeviceRef, Error> {
     let manager = managers.get(&sort).ok_or_else(|| {
         Error::new(
@@ -41,97 +166,545 @@ pub fn open_device(
     //     ));
     // }
 
+    let receive: ReceiveConfig = parse_config(config.clone())?;
+    // Fail before the device is opened
+    receive.framing.build()?;
+    CommandParser::with_syntax(receive.commands.clone())?;
+    receive.readout_parser()?;
+    receive.log_parser()?;
+
     let device = manager.open(config)?;
+    schemas.set_syntax(device.id(), receive.commands.clone());
 
     let drive = DeviceDrive {
         channel,
-        parser: CommandParser::new(),
         device: device.clone(),
-        drive: false,
+        manager: Arc::clone(manager),
+        recorder: Arc::clone(&recorder),
+        telemetry: Arc::clone(&telemetry),
+        schemas: Arc::clone(&schemas),
+        replies: Arc::clone(&replies),
+        handlers: handlers.inner().clone(),
+        receive,
+        counters: Arc::new(FrameCounters::default()),
+        stats: FrameStats::default(),
+        reader: None,
+        reconnect: None,
+        lines: None,
     };
 
     println!("Registering drive: {} ({})", name, device.id());
 
+    drive.start_on_open();
     driver.register(drive);
 
     Ok(device)
 }
 
-struct DeviceDrive {
+/// Size of the buffer a reader thread reads into
+const READ_BUFFER: usize = 64 * 1024;
+
+struct ReaderState {
+    stop: AtomicBool,
+    failed: AtomicBool,
+}
+
+///
+/// Runs on its own thread and forwards data as soon as the device delivers
+/// it. It deliberately holds no `DeviceRef`, so it never keeps a device open.
+///
+struct DeviceReader {
+    reader: Box<dyn Read + Send>,
+    device: u64,
     channel: Channel<DeviceEvent>,
+    framer: Box<dyn Framer>,
+    checksum: Option<ChecksumConfig>,
+    counters: Arc<FrameCounters>,
     parser: CommandParser,
+    /// What the parser reads, to write replies and quote commands in errors
+    syntax: CommandSyntax,
+    readouts: ReadoutParser,
+    logs: LogParser,
+    schemas: Arc<CommandSchemas>,
+    replies: Arc<Replies>,
+    handlers: CommandHandlers,
+    recorder: Arc<Recorder>,
+    telemetry: DeviceTelemetry,
+    state: Arc<ReaderState>,
+}
+
+impl DeviceReader {
+    fn run(mut self) {
+        let mut buf = vec![0u8; READ_BUFFER];
+
+        while !self.state.stop.load(Ordering::Relaxed) {
+            let len = match self.reader.read(&mut buf) {
+                Ok(len) => len,
+                Err(e) if matches!(
+                    e.kind(),
+                    io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                ) => 0,
+                Err(e) => {
+                    if !self.state.stop.load(Ordering::Relaxed) {
+                        println!("ERROR - Lost device {} because {}", self.device, e);
+                        self.state.failed.store(true, Ordering::Relaxed);
+                    }
+                    return;
+                }
+            };
+
+            if len == 0 {
+                continue;
+            }
+
+            let time = Timestamp::now();
+
+            if let Err(e) = self.forward(&buf[..len], time) {
+                println!("ERROR - Reader for device {} stopped because {}", self.device, e.message);
+                self.state.failed.store(true, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    fn forward(&mut self, content: &[u8], time: Timestamp) -> Result<(), Error> {
+        if let Err(e) = self.recorder.record(self.device, Direction::Rx, content, time) {
+            self.channel.send(DeviceEvent::Error {
+                message: e.message,
+                time,
+            })?;
+        }
+
+        self.channel.send(DeviceEvent::RecRaw {
+            content: content.to_vec(),
+            time,
+        })?;
+
+        for frame in self.framer.push(content) {
+            let (frame, valid) = match &self.checksum {
+                None => (frame, true),
+                Some(checksum) => match checksum.verify(&frame) {
+                    Some(payload) => (payload.to_vec(), true),
+                    None => (frame, false),
+                },
+            };
+
+            self.counters.count(valid);
+
+            if valid {
+                self.parser.extend(&frame)?;
+
+                for readout in self.readouts.push(&frame) {
+                    self.telemetry.record(&readout.component, readout.value, time);
+                    self.channel.send(DeviceEvent::Readout {
+                        component: readout.component,
+                        value: readout.value,
+                        ts: time,
+                    })?;
+                }
+
+                for entry in self.logs.push(&frame) {
+                    self.channel.send(DeviceEvent::Log { entry, time })?;
+                }
+            }
+
+            self.channel.send(DeviceEvent::RecFrame {
+                content: frame,
+                time,
+                valid,
+            })?;
+
+            // Earlier frames were already parsed, so whatever completes now
+            // was completed by this read
+            while let Some(parsed) = self.parser.parse() {
+                match parsed {
+                    Ok(command) => self.dispatch(command, time)?,
+                    Err(error) => self.channel.send(DeviceEvent::ParseError { error, time })?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn dispatch(&self, command: Command, time: Timestamp) -> Result<(), Error> {
+        self.replies.offer(self.device, &command);
+
+        let mut context = HandlerContext::new(self.device, time);
+
+        for handler in self.handlers.get(&command.action).into_iter().flatten() {
+            if let Err(e) = handler.handle(&command, &mut context) {
+                context.consume();
+                context.emit(DeviceEvent::ParseError {
+                    error: ParseError {
+                        message: e.message,
+                        content: command.encode_with(&self.syntax),
+                    },
+                    time,
+                });
+            }
+        }
+
+        let (replies, events, forward) = context.into_parts();
+
+        for reply in replies {
+            let reply = format!("{}\n", reply.encode_with(&self.syntax));
+
+            // A broken device is noticed by the next read
+            if let Err(e) = write_device(self.device, reply.as_bytes(), &self.recorder) {
+                self.channel.send(DeviceEvent::Error {
+                    message: e.message,
+                    time,
+                })?;
+            }
+        }
+
+        for event in events {
+            self.channel.send(event)?;
+        }
+
+        if forward {
+            self.channel.send(self.typed(command, time))?;
+        }
+
+        Ok(())
+    }
+
+    fn typed(&self, command: Command, time: Timestamp) -> DeviceEvent {
+        let schema = self.schemas.get(self.device);
+
+        match schema.as_ref().and_then(|s| s.validate(&command)) {
+            None => DeviceEvent::RecCommand { command, typed: None, time },
+            Some(Ok(typed)) => DeviceEvent::RecCommand {
+                command,
+                typed: Some(typed),
+                time,
+            },
+            Some(Err(e)) => DeviceEvent::ParseError {
+                error: ParseError {
+                    message: e.message,
+                    content: command.encode_with(&self.syntax),
+                },
+                time,
+            },
+        }
+    }
+}
+
+///
+/// A device that dropped out and is being reopened under the same reference
+///
+struct Reconnect {
+    policy: ReconnectPolicy,
+    config: Value,
+    attempt: u32,
+    next: Instant,
+    /// The attempt in progress, opening on its own thread
+    pending: Option<Attempt>,
+}
+
+/// Filled in by the thread opening the device
+type Attempt = Arc<Mutex<Option<Result<Device, Error>>>>;
+
+///
+/// Housekeeping for an open device: starts its reader once a project
+/// attaches, reconnects it if it drops out and its config opted in, and
+/// closes it once it is lost or nobody uses it.
+///
+struct DeviceDrive {
+    channel: Channel<DeviceEvent>,
     device: DeviceRef,
-    drive: bool,
+    manager: Arc<dyn DeviceManager>,
+    recorder: Arc<Recorder>,
+    telemetry: Arc<Telemetry>,
+    schemas: Arc<CommandSchemas>,
+    replies: Arc<Replies>,
+    handlers: CommandHandlers,
+    receive: ReceiveConfig,
+    counters: Arc<FrameCounters>,
+    /// Frame counts last sent to the frontend
+    stats: FrameStats,
+    reader: Option<Arc<ReaderState>>,
+    reconnect: Option<Reconnect>,
+    lines: Option<ControlLines>,
 }
 
-impl Drive for DeviceDrive {
-    fn drive(&mut self) -> Result<bool, Error> {
-        if self.device.rc() > 1 && !self.drive {
-            self.drive = true;
-            println!("Drive starting for device: {}", self.device.id());
+impl DeviceDrive {
+    ///
+    /// Runs the `on_open` sequence of the device config, if it has one, on
+    /// a blocking thread. Failures do not close the device.
+    ///
+    fn start_on_open(&self) {
+        let Some(Some(steps)) = self.device.use_device(|d| d.on_open()) else {
+            return;
+        };
+
+        let device = self.device.id();
+        let channel = self.channel.clone();
+
+        tauri::async_runtime::spawn_blocking(move || {
+            if let Err(e) = run_sequence(device, &steps) {
+                let _ = channel.send(DeviceEvent::Error {
+                    message: format!("Failed to run the on_open sequence: {}", e.message),
+                    time: Timestamp::now(),
+                });
+            }
+        });
+    }
+
+    fn start_reader(&mut self) -> Result<bool, Error> {
+        let reader = match self.device.use_device(|d| d.reader()) {
+            Some(Ok(reader)) => reader,
+            Some(Err(e)) => {
+                println!("ERROR - Lost device {} because {}", self.device.id(), e.message);
+
+                return self.lost();
+            }
+            None => {
+                self.channel.send(DeviceEvent::Close { error: false })?;
+
+                return Ok(false);
+            }
+        };
+
+        let state = Arc::new(ReaderState {
+            stop: AtomicBool::new(false),
+            failed: AtomicBool::new(false),
+        });
+
+        let reader = DeviceReader {
+            reader,
+            device: self.device.id(),
+            channel: self.channel.clone(),
+            // Validated when the device was opened, a reconnect starts afresh
+            framer: self.receive.framing.build()?,
+            checksum: self.receive.checksum.clone(),
+            counters: Arc::clone(&self.counters),
+            parser: CommandParser::with_syntax(self.receive.commands.clone())?,
+            syntax: self.receive.commands.clone(),
+            readouts: self.receive.readout_parser()?,
+            logs: self.receive.log_parser()?,
+            schemas: Arc::clone(&self.schemas),
+            replies: Arc::clone(&self.replies),
+            handlers: self.handlers.clone(),
+            recorder: Arc::clone(&self.recorder),
+            telemetry: self.telemetry.device(self.device.id()),
+            state: Arc::clone(&state),
+        };
+
+        println!("Reader starting for device: {}", self.device.id());
+        thread::spawn(move || reader.run());
+
+        if let Some(Some(address)) = self.device.use_device(|d| d.address()) {
+            self.channel.send(DeviceEvent::Address(address))?;
         }
 
-        if self.device.rc() <= 1 && self.drive {
-            println!("Closing device drive {}", self.device.rc());
+        self.reader = Some(state);
+
+        if self.receive.describe {
+            let request = format!("{}\n", help_schema().encode_with(&self.receive.commands));
+
+            // A broken device is noticed by the reader
+            if let Err(e) = write_device(self.device.id(), request.as_bytes(), &self.recorder) {
+                self.channel.send(DeviceEvent::Error {
+                    message: format!("Failed to ask the device to describe itself: {}", e.message),
+                    time: Timestamp::now(),
+                })?;
+            }
+        }
+
+        Ok(true)
+    }
+
+    fn stop_reader(&mut self) {
+        if let Some(state) = self.reader.take() {
+            state.stop.store(true, Ordering::Relaxed);
+        }
+
+        self.lines = None;
+    }
+
+    ///
+    /// Input lines have no data to wake a reader, so they are polled here.
+    /// Errors are left for the reader to notice.
+    ///
+    fn poll_lines(&mut self) -> Result<(), Error> {
+        let lines = self.device.use_device(|d| d.control_lines());
+
+        if let Some(Ok(Some(lines))) = lines
+            && self.lines != Some(lines)
+        {
+            self.lines = Some(lines);
+            self.channel.send(DeviceEvent::ControlLines(lines))?;
+        }
+
+        Ok(())
+    }
+
+    fn poll_stats(&mut self) -> Result<(), Error> {
+        if self.receive.checksum.is_none() {
+            return Ok(());
+        }
+
+        let stats = self.counters.stats();
+
+        if self.stats != stats {
+            self.stats = stats;
+            self.channel.send(DeviceEvent::FrameStats(stats))?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// The device stopped working. Starts reconnecting if its config has a
+    /// reconnect policy, otherwise closes it.
+    ///
+    fn lost(&mut self) -> Result<bool, Error> {
+        self.stop_reader();
+
+        let reconnect = self
+            .device
+            .use_device(|d| {
+                let policy = d.reconnect()?;
+                // Release the port so it can be reopened
+                d.close();
+
+                Some((policy, d.config()))
+            })
+            .flatten();
+
+        let Some((policy, config)) = reconnect else {
             self.device.close();
+            self.channel.send(DeviceEvent::Close { error: true })?;
 
-            self.channel.send(DeviceEvent::Close {error: false}).map_err(|_| {
-                Error::new(
-                    ErrorKind::TauriError,
-                    "Failed to send message through channel",
-                )
-            })?;
             return Ok(false);
+        };
+
+        self.reconnect = Some(Reconnect {
+            policy,
+            config,
+            attempt: 0,
+            next: Instant::now(),
+            pending: None,
+        });
+
+        Ok(true)
+    }
+
+    fn try_reconnect(&mut self) -> Result<bool, Error> {
+        let Some(reconnect) = self.reconnect.as_mut() else {
+            return Ok(true);
+        };
+
+        if let Some(pending) = &reconnect.pending {
+            let Some(result) = pending.lock().unwrap().take() else {
+                return Ok(true);
+            };
+            reconnect.pending = None;
+
+            return self.reconnected(result);
         }
 
-        if !self.drive {
+        if Instant::now() < reconnect.next {
             return Ok(true);
         }
 
-        let content = self.device.use_device(|d| d.read_available());
+        reconnect.attempt += 1;
+        reconnect.next = Instant::now() + Duration::from_millis(reconnect.policy.interval);
 
-        let content = if let Some(x) = content {
-            match x {
-                Ok(content) => content,
-                Err(e) => {
-                    println!("ERROR - Closed device {} because {}", self.device.id(), e.message);
+        let attempt = reconnect.attempt;
+        self.channel.send(DeviceEvent::Reconnecting { attempt })?;
+
+        // Opening can take a while (e.g. a TCP connect timing out), the
+        // device is only replaced back on this thread
+        let pending = Attempt::default();
+        let manager = Arc::clone(&self.manager);
+        let config = reconnect.config.clone();
+
+        thread::spawn({
+            let pending = Arc::clone(&pending);
+            move || *pending.lock().unwrap() = Some(manager.connect(config))
+        });
+        reconnect.pending = Some(pending);
+
+        Ok(true)
+    }
+
+    fn reconnected(&mut self, result: Result<Device, Error>) -> Result<bool, Error> {
+        let Some(reconnect) = self.reconnect.as_mut() else {
+            return Ok(true);
+        };
+
+        match result {
+            Ok(device) => {
+                if !self.device.replace(device) {
+                    self.channel.send(DeviceEvent::Close { error: false })?;
+
+                    return Ok(false);
+                }
+
+                self.reconnect = None;
+                self.channel.send(DeviceEvent::Reconnected)?;
+                self.start_on_open();
+
+                // The reader restarts on the next pass
+                Ok(true)
+            }
+            Err(e) => {
+                let attempt = reconnect.attempt;
+
+                self.channel.send(DeviceEvent::Error {
+                    message: format!("Reconnect attempt {} failed: {}", attempt, e.message),
+                    time: Timestamp::now(),
+                })?;
+
+                if reconnect.policy.max_attempts.is_some_and(|max| attempt >= max) {
+                    self.reconnect = None;
                     self.device.close();
-                    self.channel.send(DeviceEvent::Close {error: true})?;
+                    self.channel.send(DeviceEvent::Close { error: true })?;
 
                     return Ok(false);
                 }
+
+                Ok(true)
             }
-        } else {
-            self.channel.send(DeviceEvent::Close {error: false})?;
+        }
+    }
+}
+
+impl Drive for DeviceDrive {
+    fn drive(&mut self) -> Result<bool, Error> {
+        let started = self.reader.is_some() || self.reconnect.is_some();
+
+        if self.device.rc() <= 1 && started {
+            println!("Closing device drive {}", self.device.rc());
+            self.stop_reader();
+            self.device.close();
 
+            self.channel.send(DeviceEvent::Close { error: false })?;
             return Ok(false);
-        };
+        }
 
-        self.parser.extend(content.as_slice())?;
+        if self.reconnect.is_some() {
+            return self.try_reconnect();
+        }
 
-        self.channel
-            .send(DeviceEvent::RecRaw(content.clone()))
-            .map_err(|_| {
-                Error::new(
-                    ErrorKind::TauriError,
-                    "Failed to send message through channel",
-                )
-            })?;
+        if self.device.rc() > 1 && self.reader.is_none() {
+            return self.start_reader();
+        }
 
-        let mut parsing = true;
-        while parsing {
-            if let Some(c) = self.parser.parse() {
-                self.channel
-                    .send(DeviceEvent::RecCommand(c))
-                    .map_err(|_| {
-                        Error::new(
-                            ErrorKind::TauriError,
-                            "Failed to send message through channel",
-                        )
-                    })?;
-            } else {
-                parsing = false
-            }
+        if self
+            .reader
+            .as_ref()
+            .is_some_and(|r| r.failed.load(Ordering::Relaxed))
+        {
+            return self.lost();
+        }
+
+        if self.reader.is_some() {
+            self.poll_lines()?;
+            self.poll_stats()?;
         }
 
         Ok(true)
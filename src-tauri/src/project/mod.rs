@@ -1,14 +1,23 @@
 mod drive;
 
+pub use drive::DeviceEvent;
+
 use crate::config::BuilderConfig;
 use crate::device::DeviceRef;
 use crate::err::{Error, ErrorKind};
 use std::collections::HashMap;
 use std::process::id;
 use std::sync::atomic::AtomicU64;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tauri::{command, generate_handler, State};
+use crate::capture::Direction;
+use crate::command::schema::CommandSchemas;
+use crate::command::CommandParser;
 use crate::device_pool;
+use crate::recording::Recorder;
+use crate::telemetry::Telemetry;
+use crate::timestamp::Timestamp;
+use crate::workspace::WorkspaceHandler;
 
 pub type Projects = Mutex<HashMap<u64, Project>>;
 
@@ -29,6 +38,26 @@ impl Project {
             device,
         }
     }
+
+    pub fn device(&self) -> Option<&DeviceRef> {
+        self.device.as_ref()
+    }
+
+    pub fn workspace(&self) -> &str {
+        &self.workspace
+    }
+
+    ///
+    /// Validates commands on the attached device against the command panels
+    /// of this project's workspace
+    ///
+    pub fn attach_schema(&self, workspaces: &Mutex<WorkspaceHandler>, schemas: &CommandSchemas) {
+        if let Some(device) = self.device.as_ref()
+            && let Some(workspace) = workspaces.lock().unwrap().get(&self.workspace)
+        {
+            schemas.set(device.id(), workspace.command_schema());
+        }
+    }
 }
 
 impl<R: tauri::Runtime> BuilderConfig<R> {
@@ -60,10 +89,15 @@ fn new_project(
     workspace: String,
     reference: Option<DeviceRef>,
     projects: State<Projects>,
+    workspaces: State<Arc<Mutex<WorkspaceHandler>>>,
+    schemas: State<Arc<CommandSchemas>>,
+    telemetry: State<Arc<Telemetry>>,
 ) -> Result<u64, Error> {
     let project = Project::new(workspace, reference);
+    project.attach_schema(&workspaces, &schemas);
 
     let id = project.id;
+    telemetry.attach(id, project.device.as_ref().map(|d| d.id()));
     projects.lock().unwrap().insert(id, project);
 
     Ok(id)
@@ -74,6 +108,10 @@ fn push_device(
     project: u64,
     reference: DeviceRef,
     projects: State<Projects>,
+    workspaces: State<Arc<Mutex<WorkspaceHandler>>>,
+    schemas: State<Arc<CommandSchemas>>,
+    telemetry: State<Arc<Telemetry>>,
+    recorder: State<Arc<Recorder>>,
 ) -> Result<(), Error> {
     let mut guard = projects.lock().unwrap();
     let project = guard.get_mut(&project).ok_or(Error::new(
@@ -81,42 +119,94 @@ fn push_device(
         "Failed to find this project",
     ))?;
 
+    telemetry.attach(project.id, Some(reference.id()));
+    recorder.attach(project.id, reference.id());
     project.device = Some(reference);
+    project.attach_schema(&workspaces, &schemas);
 
     Ok(())
 }
 
 #[command]
-fn device_write(project_id: u64, buf: Vec<u8>, projects: State<Projects>) -> Result<(), Error> {
+fn device_write(
+    project_id: u64,
+    buf: Vec<u8>,
+    projects: State<Projects>,
+    recorder: State<Arc<Recorder>>,
+    schemas: State<Arc<CommandSchemas>>,
+) -> Result<Timestamp, Error> {
     let guard = projects.lock().unwrap();
     let project = guard
         .get(&project_id)
         .ok_or_else(|| Error::new(ErrorKind::NoSuchProject, "Cannot find this project."))?;
 
-    if let Some(d_ref) = project.device.as_ref() {
-        if let Some(x) = d_ref.use_device(|d| d.write(buf.as_slice())) {
-            x?;
+    if let Some(d_ref) = project.device.as_ref()
+        && let Some(schema) = schemas.get(d_ref.id())
+    {
+        // Reject malformed commands before any of them reach the device
+        let mut parser = CommandParser::with_syntax(schemas.syntax(d_ref.id()))?;
+        parser.extend(&buf)?;
+
+        while let Some(parsed) = parser.parse() {
+            if let Ok(command) = parsed
+                && let Some(Err(e)) = schema.validate(&command)
+            {
+                return Err(e);
+            }
         }
     }
 
+    match project.device.as_ref() {
+        Some(d_ref) => write_device(d_ref.id(), &buf, &recorder),
+        None => Ok(Timestamp::now()),
+    }
+}
 
-    Ok(())
+///
+/// Writes to a device and records what was written, returning when. A failed
+/// recording is an error too, even though the device got the data.
+///
+pub fn write_device(device: u64, buf: &[u8], recorder: &Recorder) -> Result<Timestamp, Error> {
+    let time = Timestamp::now();
+
+    if let Some(x) = device_pool!().use_id(device, |d| d.write(buf)) {
+        let written = x?;
+        recorder.record(device, Direction::Tx, &buf[..written], time)?;
+    }
+
+    Ok(time)
 }
 
 #[command]
-fn close_project(project_id: u64, projects: State<Projects>) -> Result<(), Error> {
+fn close_project(
+    project_id: u64,
+    projects: State<Projects>,
+    recorder: State<Arc<Recorder>>,
+    telemetry: State<Arc<Telemetry>>,
+) -> Result<(), Error> {
     let mut guard = projects.lock().unwrap();
 
     println!("Projects: {:?}", guard);
     guard.remove(&project_id);
     println!("Projects: {:?}", guard);
 
+    drop(guard);
+    telemetry.remove(project_id);
+
+    // The project is closed even if its recording cannot be finished
+    recorder.stop(project_id)?;
+
     Ok(())
 }
 
 #[command]
-fn close_all_projects(projects: State<Projects>) -> Result<(), Error> {
+fn close_all_projects(
+    projects: State<Projects>,
+    recorder: State<Arc<Recorder>>,
+    telemetry: State<Arc<Telemetry>>,
+) -> Result<(), Error> {
     let mut guard = projects.lock().unwrap();
+    let ids = guard.keys().copied().collect::<Vec<u64>>();
 
     println!("Closing all projects ({})", guard.len());
 
@@ -129,8 +219,20 @@ fn close_all_projects(projects: State<Projects>) -> Result<(), Error> {
     });
 
     guard.clear();
+    drop(guard);
     println!("--- Open Devices ---");
     println!("{:?}", device_pool!().list());
 
-    Ok(())
+    // Every recording is finished before the first error is reported
+    let mut failed = None;
+
+    for id in ids {
+        telemetry.remove(id);
+
+        if let Err(e) = recorder.stop(id) {
+            failed.get_or_insert(e);
+        }
+    }
+
+    failed.map_or(Ok(()), Err)
 }
@@ -0,0 +1,255 @@
+use crate::err::Error;
+use crate::framing::{DEFAULT_MAX_LENGTH, Framer, NewlineFramer};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// A decimal number, optionally signed and with an exponent
+const NUMBER: &str = r"[-+]?(?:\d+(?:\.\d*)?|\.\d+)(?:[eE][-+]?\d+)?";
+
+///
+/// One sample of a component, e.g. `rpm = 1200`
+///
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct Readout {
+    pub component: String,
+    pub value: f64,
+}
+
+///
+/// The `readouts` section of a device config
+///
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReadoutFormat {
+    /// Nothing is parsed
+    None,
+    /// `component = value` pairs anywhere in a line. The separator may be
+    /// e.g. `:`, or a space for `$rpm 1200` with a `$` prefix.
+    KeyValue {
+        #[serde(default = "default_separator")]
+        separator: String,
+        /// Keys only count if they start with this, it is not part of the
+        /// component name
+        #[serde(default)]
+        prefix: String,
+    },
+    /// Lines of delimited values, one column per component. Empty column
+    /// names are skipped, lines that do not fit are ignored.
+    Csv {
+        columns: Vec<String>,
+        #[serde(default = "default_delimiter")]
+        delimiter: char,
+    },
+}
+
+fn default_separator() -> String {
+    "=".to_string()
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+impl Default for ReadoutFormat {
+    /// What the readout widgets used to look for
+    fn default() -> Self {
+        ReadoutFormat::KeyValue {
+            separator: default_separator(),
+            prefix: String::new(),
+        }
+    }
+}
+
+enum Matcher {
+    None,
+    KeyValue(Regex),
+    Csv {
+        columns: Vec<String>,
+        delimiter: char,
+    },
+}
+
+///
+/// Finds readouts in received frames. Unframed data is cut into lines
+/// first, so a sample split across reads is still found once.
+///
+pub struct ReadoutParser {
+    matcher: Matcher,
+    lines: Option<NewlineFramer>,
+}
+
+impl ReadoutFormat {
+    /// `framed` is false if frames are arbitrary chunks of the stream
+    pub fn build(&self, framed: bool) -> Result<ReadoutParser, Error> {
+        let matcher = match self {
+            ReadoutFormat::None => Matcher::None,
+            ReadoutFormat::KeyValue { separator, prefix } => {
+                if separator.is_empty() {
+                    return Err(Error::invalid_config(
+                        "readouts.separator",
+                        "must not be empty",
+                    ));
+                }
+
+                // Keys cannot contain whitespace or the separator
+                let excluded = separator
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .map(|c| regex::escape(&c.to_string()))
+                    .collect::<String>();
+
+                let separator = if separator.trim().is_empty() {
+                    r"\s+".to_string()
+                } else {
+                    format!(r"\s*{}\s*", regex::escape(separator.trim()))
+                };
+
+                let pattern = format!(
+                    r"{}([^\s{}]+){}({})",
+                    regex::escape(prefix),
+                    excluded,
+                    separator,
+                    NUMBER
+                );
+
+                Matcher::KeyValue(Regex::new(&pattern).map_err(|e| {
+                    Error::invalid_config("readouts", format!("cannot be matched: {}", e))
+                })?)
+            }
+            ReadoutFormat::Csv { columns, delimiter } => {
+                if columns.iter().all(|c| c.is_empty()) {
+                    return Err(Error::invalid_config(
+                        "readouts.columns",
+                        "must name a column",
+                    ));
+                }
+
+                Matcher::Csv {
+                    columns: columns.clone(),
+                    delimiter: *delimiter,
+                }
+            }
+        };
+
+        Ok(ReadoutParser {
+            matcher,
+            lines: (!framed).then(|| NewlineFramer::new(b"\n", DEFAULT_MAX_LENGTH)),
+        })
+    }
+}
+
+impl ReadoutParser {
+    pub fn push(&mut self, frame: &[u8]) -> Vec<Readout> {
+        if let Matcher::None = self.matcher {
+            return vec![];
+        }
+
+        let lines = match self.lines.as_mut() {
+            Some(lines) => lines.push(frame),
+            None => vec![frame.to_vec()],
+        };
+
+        lines
+            .iter()
+            .flat_map(|line| self.parse(&String::from_utf8_lossy(line)))
+            .collect()
+    }
+
+    fn parse(&self, line: &str) -> Vec<Readout> {
+        match &self.matcher {
+            Matcher::None => vec![],
+            Matcher::KeyValue(pattern) => pattern
+                .captures_iter(line)
+                .filter_map(|c| {
+                    Some(Readout {
+                        component: c[1].to_string(),
+                        value: c[2].parse().ok()?,
+                    })
+                })
+                .collect(),
+            Matcher::Csv { columns, delimiter } => {
+                let values = line
+                    .trim()
+                    .split(*delimiter)
+                    .map(|v| v.trim().parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>();
+
+                match values {
+                    Ok(values) if values.len() == columns.len() => columns
+                        .iter()
+                        .zip(values)
+                        .filter(|(c, _)| !c.is_empty())
+                        .map(|(c, value)| Readout {
+                            component: c.clone(),
+                            value,
+                        })
+                        .collect(),
+                    // Headers and anything else
+                    _ => vec![],
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Readout, ReadoutFormat};
+    use serde_json::json;
+
+    fn readout(component: &str, value: f64) -> Readout {
+        Readout {
+            component: component.to_string(),
+            value,
+        }
+    }
+
+    #[test]
+    fn test_key_value() {
+        let mut parser = ReadoutFormat::default().build(false).unwrap();
+
+        assert_eq!(
+            parser.push(b"rpm = 1200 torque=-3.5e2\ntemp = 2"),
+            vec![readout("rpm", 1200.0), readout("torque", -350.0)]
+        );
+        assert_eq!(
+            parser.push(b"5.5C [log info \"x = y\"]\n"),
+            vec![readout("temp", 25.5)]
+        );
+
+        let format: ReadoutFormat =
+            serde_json::from_value(json!({"type": "key_value", "separator": ":"})).unwrap();
+        let mut parser = format.build(true).unwrap();
+        assert_eq!(
+            parser.push(b"rpm:12 volts: .5"),
+            vec![readout("rpm", 12.0), readout("volts", 0.5)]
+        );
+
+        let format: ReadoutFormat =
+            serde_json::from_value(json!({"type": "key_value", "separator": " ", "prefix": "$"}))
+                .unwrap();
+        let mut parser = format.build(true).unwrap();
+        assert_eq!(
+            parser.push(b"$rpm 12 rpm 13 $temp +4"),
+            vec![readout("rpm", 12.0), readout("temp", 4.0)]
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let format: ReadoutFormat =
+            serde_json::from_value(json!({"type": "csv", "columns": ["rpm", "", "temp"]})).unwrap();
+        let mut parser = format.build(true).unwrap();
+
+        assert_eq!(parser.push(b"rpm,time,temp"), vec![]);
+        assert_eq!(
+            parser.push(b"1200, 5, -2.5e1\r"),
+            vec![readout("rpm", 1200.0), readout("temp", -25.0)]
+        );
+        assert_eq!(parser.push(b"1200,5"), vec![]);
+
+        let bad: ReadoutFormat =
+            serde_json::from_value(json!({"type": "csv", "columns": []})).unwrap();
+        assert!(bad.build(true).is_err());
+    }
+}
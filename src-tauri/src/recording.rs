@@ -0,0 +1,322 @@
+use crate::capture::{CaptureWriter, Direction, Record, EXTENSION};
+use crate::config::BuilderConfig;
+use crate::err::{Error, ErrorKind};
+use crate::timestamp::{monotonic_now, unix_now, Timestamp};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::generate_handler;
+
+struct Recording {
+    device: u64,
+    path: PathBuf,
+    /// Monotonic time the recording started at
+    start: Duration,
+    writer: CaptureWriter<BufWriter<File>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingInfo {
+    name: String,
+    path: String,
+    size: u64,
+    modified: u64,
+    active: bool,
+}
+
+///
+/// Writes device traffic of recording projects to capture files, one per
+/// project, in the recordings folder.
+///
+pub struct Recorder {
+    path: PathBuf,
+    recordings: Mutex<HashMap<u64, Recording>>,
+}
+
+impl Recorder {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Recorder {
+        Recorder {
+            path: path.into(),
+            recordings: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn start(&self, project: u64, device: u64, name: &str) -> Result<RecordingInfo, Error> {
+        let mut recordings = self.recordings.lock().unwrap();
+
+        if recordings.contains_key(&project) {
+            return Err(Error::new(
+                ErrorKind::AlreadyOpen,
+                "This project is already recording",
+            ));
+        }
+
+        fs::create_dir_all(&self.path)?;
+
+        let name = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .collect::<String>();
+        let path = self.path.join(format!(
+            "{}-{}.{}",
+            name.trim_matches('_'),
+            unix_now().as_millis(),
+            EXTENSION
+        ));
+
+        let recording = Recording {
+            device,
+            path: path.clone(),
+            start: monotonic_now(),
+            writer: CaptureWriter::create(&path)?,
+        };
+
+        recordings.insert(project, recording);
+        drop(recordings);
+
+        self.info(&path)
+    }
+
+    pub fn stop(&self, project: u64) -> Result<Option<RecordingInfo>, Error> {
+        let recording = self.recordings.lock().unwrap().remove(&project);
+
+        if let Some(mut recording) = recording {
+            recording.writer.flush()?;
+
+            Ok(Some(self.info(&recording.path)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    ///
+    /// Follows a recording project to the device pushed to it, the capture
+    /// goes on in the same file
+    ///
+    pub fn attach(&self, project: u64, device: u64) {
+        if let Some(recording) = self.recordings.lock().unwrap().get_mut(&project) {
+            recording.device = device;
+        }
+    }
+
+    ///
+    /// Appends traffic of a device to every recording attached to it, at the
+    /// time it was read or written. A recording that fails does not keep the
+    /// others from being written, its error is returned once they are.
+    ///
+    pub fn record(
+        &self,
+        device: u64,
+        direction: Direction,
+        data: &[u8],
+        time: Timestamp,
+    ) -> Result<(), Error> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let mut failed = None;
+
+        for recording in self
+            .recordings
+            .lock()
+            .unwrap()
+            .values_mut()
+            .filter(|r| r.device == device)
+        {
+            let record = Record {
+                direction,
+                monotonic: time.monotonic.saturating_sub(recording.start),
+                unix: time.unix,
+                data: data.to_vec(),
+            };
+
+            // Flush every record, recordings must survive a crash
+            if let Err(e) = recording
+                .writer
+                .write(&record)
+                .and_then(|_| recording.writer.flush())
+            {
+                failed = Some(Error::new(
+                    ErrorKind::IO,
+                    format!("Failed to record to {}: {}", recording.path.display(), e),
+                ));
+            }
+        }
+
+        failed.map_or(Ok(()), Err)
+    }
+
+    pub fn list(&self) -> Result<Vec<RecordingInfo>, Error> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut recordings = fs::read_dir(&self.path)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|e| e == EXTENSION))
+            .map(|p| self.info(&p))
+            .collect::<Result<Vec<RecordingInfo>, Error>>()?;
+
+        recordings.sort_by_key(|r| Reverse(r.modified));
+
+        Ok(recordings)
+    }
+
+    fn info(&self, path: &PathBuf) -> Result<RecordingInfo, Error> {
+        let metadata = fs::metadata(path)?;
+        let active = self
+            .recordings
+            .lock()
+            .unwrap()
+            .values()
+            .any(|r| &r.path == path);
+
+        Ok(RecordingInfo {
+            name: path
+                .file_stem()
+                .map_or(String::new(), |n| n.to_string_lossy().to_string()),
+            path: path.to_string_lossy().to_string(),
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |m| m.as_millis() as u64),
+            active,
+        })
+    }
+}
+
+impl<R: tauri::Runtime> BuilderConfig<R> {
+    pub fn recorder<T: Into<PathBuf>>(self, path: T) -> BuilderConfig<R> {
+        let recorder = Arc::new(Recorder::new(path));
+
+        self.register_commands(
+            generate_handler![
+                routes::recording_start,
+                routes::recording_stop,
+                routes::recording_ls
+            ],
+            &["recording_start", "recording_stop", "recording_ls"],
+        )
+        .fold(|b| b.manage(recorder))
+    }
+}
+
+mod routes {
+    use crate::err::{Error, ErrorKind};
+    use crate::project::Projects;
+    use crate::recording::{Recorder, RecordingInfo};
+    use std::sync::Arc;
+    use tauri::State;
+
+    #[tauri::command]
+    pub fn recording_start(
+        project_id: u64,
+        projects: State<'_, Projects>,
+        recorder: State<'_, Arc<Recorder>>,
+    ) -> Result<RecordingInfo, Error> {
+        let guard = projects.lock().unwrap();
+        let project = guard
+            .get(&project_id)
+            .ok_or_else(|| Error::new(ErrorKind::NoSuchProject, "Cannot find this project."))?;
+
+        let device = project
+            .device()
+            .ok_or_else(|| Error::new(ErrorKind::NoSuchDevice, "This project has no device."))?;
+
+        let name = device
+            .use_device(|d| d.name.clone())
+            .ok_or_else(|| Error::new(ErrorKind::NoSuchDevice, "This device is not open."))?;
+
+        recorder.start(project_id, device.id(), &name)
+    }
+
+    #[tauri::command]
+    pub fn recording_stop(
+        project_id: u64,
+        recorder: State<'_, Arc<Recorder>>,
+    ) -> Result<Option<RecordingInfo>, Error> {
+        recorder.stop(project_id)
+    }
+
+    #[tauri::command]
+    pub fn recording_ls(recorder: State<'_, Arc<Recorder>>) -> Result<Vec<RecordingInfo>, Error> {
+        recorder.list()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Recorder;
+    use crate::capture::{CaptureReader, Direction};
+    use crate::timestamp::Timestamp;
+    use std::time::Duration;
+
+    #[test]
+    fn test_record_device_traffic() {
+        let path = std::env::temp_dir().join("serialdurbugger-recording-test");
+        let _ = std::fs::remove_dir_all(&path);
+
+        let recorder = Recorder::new(&path);
+        let info = recorder.start(1, 7, "/dev/ttyACM0").unwrap();
+        assert!(info.active);
+
+        let time = Timestamp::now();
+        let later = Timestamp {
+            monotonic: time.monotonic + Duration::from_millis(5),
+            unix: time.unix + Duration::from_millis(5),
+        };
+
+        recorder.record(7, Direction::Rx, b"torque = 3\n", time).unwrap();
+        recorder.record(8, Direction::Rx, b"other device\n", time).unwrap();
+        recorder.record(7, Direction::Tx, b"[ping]\n", later).unwrap();
+
+        let info = recorder.stop(1).unwrap().unwrap();
+        assert!(!info.active);
+        assert!(info.name.starts_with("dev_ttyACM0-"));
+
+        let records = CaptureReader::open(&info.path).unwrap().records().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].data, b"torque = 3\n");
+        assert_eq!(records[1].direction, Direction::Tx);
+        assert_eq!(records[1].monotonic - records[0].monotonic, Duration::from_millis(5));
+        assert_eq!(records[0].unix.as_micros(), time.unix.as_micros());
+
+        assert_eq!(recorder.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_follow_pushed_device() {
+        let path = std::env::temp_dir().join("serialdurbugger-recording-push-test");
+        let _ = std::fs::remove_dir_all(&path);
+
+        let recorder = Recorder::new(&path);
+        recorder.start(1, 7, "mock").unwrap();
+
+        let time = Timestamp::now();
+
+        recorder.record(7, Direction::Rx, b"before\n", time).unwrap();
+        recorder.attach(1, 8);
+        // Not attached to any project
+        recorder.attach(2, 7);
+        recorder.record(7, Direction::Rx, b"old device\n", time).unwrap();
+        recorder.record(8, Direction::Rx, b"after\n", time).unwrap();
+
+        let info = recorder.stop(1).unwrap().unwrap();
+
+        let records = CaptureReader::open(&info.path).unwrap().records().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].data, b"before\n");
+        assert_eq!(records[1].data, b"after\n");
+    }
+}
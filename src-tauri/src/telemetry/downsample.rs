@@ -0,0 +1,206 @@
+use crate::telemetry::{Sample, Samples};
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::time::Duration;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Downsampling {
+    /// One min/max/avg bucket per pixel
+    Buckets,
+    /// Largest-Triangle-Three-Buckets, a line of at most one sample per pixel
+    /// that keeps its peaks
+    Lttb,
+}
+
+///
+/// The samples of a component read during a slice of a plot window
+///
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct Bucket {
+    /// First and last sample in the bucket
+    pub from: Timestamp,
+    pub to: Timestamp,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub count: usize,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(tag = "type", content = "data")]
+pub enum Series {
+    Buckets(Vec<Bucket>),
+    Lttb(Vec<Sample>),
+}
+
+///
+/// Cuts `from..=to` into `width` equally long slices and summarises the
+/// samples in each. Slices without samples are left out.
+///
+pub fn buckets(
+    samples: &Samples,
+    range: Range<usize>,
+    from: Duration,
+    to: Duration,
+    width: usize,
+) -> Vec<Bucket> {
+    let span = (to.saturating_sub(from).as_nanos() + 1) as f64;
+    let mut buckets: Vec<Bucket> = Vec::new();
+    let mut current = None;
+
+    for sample in samples.range(range) {
+        let offset = sample.time.monotonic.saturating_sub(from).as_nanos() as f64;
+        let index = ((offset / span) * width as f64) as usize;
+
+        match buckets.last_mut() {
+            Some(bucket) if current == Some(index) => {
+                bucket.to = sample.time;
+                bucket.min = bucket.min.min(sample.value);
+                bucket.max = bucket.max.max(sample.value);
+                bucket.avg += (sample.value - bucket.avg) / (bucket.count + 1) as f64;
+                bucket.count += 1;
+            }
+            _ => {
+                current = Some(index);
+                buckets.push(Bucket {
+                    from: sample.time,
+                    to: sample.time,
+                    min: sample.value,
+                    max: sample.value,
+                    avg: sample.value,
+                    count: 1,
+                });
+            }
+        }
+    }
+
+    buckets
+}
+
+///
+/// Picks at most `threshold` samples of a range so the line through them
+/// looks like the line through all of them. Keeps the first and last sample.
+///
+pub fn lttb(samples: &Samples, range: Range<usize>, threshold: usize) -> Vec<Sample> {
+    let n = range.len();
+
+    if n <= threshold || n <= 2 {
+        return samples.range(range).collect();
+    }
+
+    let at = |i: usize| samples.get(range.start + i);
+    let x = |s: &Sample| s.time.monotonic.as_secs_f64();
+
+    if threshold < 3 {
+        return [at(0), at(n - 1)][..threshold.max(1)].to_vec();
+    }
+
+    let every = (n - 2) as f64 / (threshold - 2) as f64;
+    let mut picked = Vec::with_capacity(threshold);
+    let mut a = at(0);
+
+    picked.push(a);
+
+    for i in 0..threshold - 2 {
+        // The average of the next bucket is the third corner
+        let next =
+            ((i + 1) as f64 * every) as usize + 1..(((i + 2) as f64 * every) as usize + 1).min(n);
+        let count = next.len().max(1) as f64;
+        let (avg_x, avg_y) = next
+            .clone()
+            .map(at)
+            .fold((0.0, 0.0), |(sx, sy), s| (sx + x(&s), sy + s.value));
+        let (avg_x, avg_y) = (avg_x / count, avg_y / count);
+
+        let bucket = (i as f64 * every) as usize + 1..((i + 1) as f64 * every) as usize + 1;
+
+        let mut best = at(bucket.start);
+        let mut best_area = -1.0;
+
+        for s in bucket.map(at) {
+            let area =
+                ((x(&a) - avg_x) * (s.value - a.value) - (x(&a) - x(&s)) * (avg_y - a.value)).abs();
+
+            if area > best_area {
+                best_area = area;
+                best = s;
+            }
+        }
+
+        picked.push(best);
+        a = best;
+    }
+
+    picked.push(at(n - 1));
+
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{buckets, lttb};
+    use crate::telemetry::Samples;
+    use crate::timestamp::Timestamp;
+    use std::time::Duration;
+
+    fn series(values: &[f64]) -> Samples {
+        let at = |i: usize| Timestamp {
+            monotonic: Duration::from_millis(i as u64),
+            unix: Duration::from_millis(i as u64),
+        };
+
+        let mut samples = Samples::new(at(0));
+
+        for (i, value) in values.iter().enumerate() {
+            samples.push(at(i), *value, usize::MAX);
+        }
+
+        samples
+    }
+
+    #[test]
+    fn test_buckets() {
+        let samples = series(&[1.0, 5.0, 3.0, 2.0, 2.0, 8.0]);
+        let summary = buckets(&samples, 0..6, Duration::ZERO, Duration::from_millis(5), 3);
+
+        assert_eq!(summary.len(), 3);
+        assert_eq!(
+            (summary[0].min, summary[0].max, summary[0].avg),
+            (1.0, 5.0, 3.0)
+        );
+        assert_eq!(summary[1].count, 2);
+        assert_eq!(summary[2].to.monotonic, Duration::from_millis(5));
+
+        // Empty slices are left out
+        let sparse = buckets(
+            &samples,
+            0..6,
+            Duration::ZERO,
+            Duration::from_millis(59),
+            10,
+        );
+        assert_eq!(sparse.len(), 1);
+        assert_eq!(sparse[0].count, 6);
+    }
+
+    #[test]
+    fn test_lttb() {
+        let mut values = vec![0.0; 100];
+        values[37] = 10.0;
+        values[80] = -4.0;
+        let samples = series(&values);
+
+        let picked = lttb(&samples, 0..100, 10);
+        assert_eq!(picked.len(), 10);
+        assert_eq!(picked[0].time.monotonic, Duration::ZERO);
+        assert_eq!(picked[9].time.monotonic, Duration::from_millis(99));
+        // Peaks survive
+        assert!(picked.iter().any(|s| s.value == 10.0));
+        assert!(picked.iter().any(|s| s.value == -4.0));
+
+        assert_eq!(lttb(&samples, 10..15, 10).len(), 5);
+        assert_eq!(lttb(&samples, 0..100, 2).len(), 2);
+    }
+}
@@ -0,0 +1,568 @@
+mod downsample;
+
+use crate::config::BuilderConfig;
+use crate::timestamp::Timestamp;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::generate_handler;
+
+pub use downsample::{Downsampling, Series};
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct Sample {
+    pub time: Timestamp,
+    pub value: f64,
+}
+
+/// A sample as it is kept, 16 bytes rather than the 40 of a `Sample`
+#[derive(Clone, Copy)]
+struct Point {
+    /// Nanoseconds after the first sample of the component
+    offset: u64,
+    value: f64,
+}
+
+///
+/// Samples of a component, oldest first. Times are kept as offsets from the
+/// first sample, which is also where the unix time of a sample is counted
+/// from.
+///
+struct Samples {
+    base: Timestamp,
+    points: VecDeque<Point>,
+}
+
+impl Samples {
+    fn new(base: Timestamp) -> Samples {
+        Samples {
+            base,
+            points: VecDeque::new(),
+        }
+    }
+
+    /// Appends a sample, dropping the oldest ones to keep at most `limit`
+    fn push(&mut self, time: Timestamp, value: f64, limit: usize) {
+        while self.points.len() >= limit.max(1) {
+            self.points.pop_front();
+        }
+
+        let offset = time.monotonic.saturating_sub(self.base.monotonic);
+
+        self.points.push_back(Point {
+            offset: offset.as_nanos() as u64,
+            value,
+        });
+    }
+
+    fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    fn monotonic(&self, index: usize) -> Duration {
+        self.base.monotonic + Duration::from_nanos(self.points[index].offset)
+    }
+
+    fn get(&self, index: usize) -> Sample {
+        let point = self.points[index];
+        let offset = Duration::from_nanos(point.offset);
+
+        Sample {
+            time: Timestamp {
+                monotonic: self.base.monotonic + offset,
+                unix: self.base.unix + offset,
+            },
+            value: point.value,
+        }
+    }
+
+    fn range(&self, range: Range<usize>) -> impl Iterator<Item = Sample> + '_ {
+        range.map(|i| self.get(i))
+    }
+
+    fn last(&self) -> Option<Sample> {
+        self.len().checked_sub(1).map(|i| self.get(i))
+    }
+
+    /// Indices of the samples read between two monotonic times, inclusive
+    fn window(&self, from: Option<Duration>, to: Option<Duration>) -> Range<usize> {
+        let time = |point: &Point| self.base.monotonic + Duration::from_nanos(point.offset);
+
+        // Samples are appended as they are read, so they are sorted
+        let start = from.map_or(0, |from| self.points.partition_point(|p| time(p) < from));
+        let end = to.map_or(self.len(), |to| self.points.partition_point(|p| time(p) <= to));
+
+        start..end.max(start)
+    }
+}
+
+/// Every component has its own lock, so summarising one holds up neither
+/// the others nor the store
+type Channel = Arc<Mutex<Samples>>;
+
+struct ProjectTelemetry {
+    device: Option<u64>,
+    channels: HashMap<String, Channel>,
+}
+
+///
+/// Keeps the latest readouts of every project, so widgets can show history
+/// from before they were mounted. Each component keeps at most `capacity`
+/// samples, and all of them together about `budget` bytes. The oldest
+/// samples are dropped first, a component that stopped reporting is only
+/// trimmed once it reports again.
+///
+pub struct Telemetry {
+    capacity: usize,
+    budget: usize,
+    projects: Mutex<HashMap<u64, ProjectTelemetry>>,
+    /// Number of components over all projects, which share the budget
+    channels: AtomicUsize,
+    /// Changes whenever a project attaches, swaps or drops its device
+    generation: AtomicU64,
+}
+
+impl Telemetry {
+    pub fn new(capacity: usize, budget: usize) -> Telemetry {
+        Telemetry {
+            capacity,
+            budget,
+            projects: Mutex::new(HashMap::new()),
+            channels: AtomicUsize::new(0),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Starts collecting the readouts of a device for a project, keeping
+    /// what it collected so far
+    pub fn attach(&self, project: u64, device: Option<u64>) {
+        let mut projects = self.projects.lock().unwrap();
+
+        projects
+            .entry(project)
+            .or_insert_with(|| ProjectTelemetry {
+                device: None,
+                channels: HashMap::new(),
+            })
+            .device = device;
+
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    pub fn remove(&self, project: u64) {
+        let mut projects = self.projects.lock().unwrap();
+
+        if let Some(project) = projects.remove(&project) {
+            self.channels.fetch_sub(project.channels.len(), Ordering::Relaxed);
+        }
+
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+
+    /// Records the readouts of a device, see `DeviceTelemetry`
+    pub fn device(self: &Arc<Self>, device: u64) -> DeviceTelemetry {
+        DeviceTelemetry {
+            telemetry: Arc::clone(self),
+            device,
+            generation: None,
+            channels: HashMap::new(),
+        }
+    }
+
+    /// The channels of a component in every project attached to a device,
+    /// created where missing
+    fn channels(&self, device: u64, component: &str, time: Timestamp) -> Vec<Channel> {
+        self.projects
+            .lock()
+            .unwrap()
+            .values_mut()
+            .filter(|p| p.device == Some(device))
+            .map(|p| {
+                let channel = p.channels.entry(component.to_string()).or_insert_with(|| {
+                    self.channels.fetch_add(1, Ordering::Relaxed);
+                    Arc::new(Mutex::new(Samples::new(time)))
+                });
+
+                Arc::clone(channel)
+            })
+            .collect()
+    }
+
+    /// Samples each component may keep
+    fn limit(&self) -> usize {
+        let channels = self.channels.load(Ordering::Relaxed).max(1);
+
+        self.capacity.min(self.budget / size_of::<Point>() / channels)
+    }
+
+    ///
+    /// Samples of a component read between two monotonic times, inclusive.
+    /// Either end may be left open.
+    ///
+    pub fn range(
+        &self,
+        project: u64,
+        component: &str,
+        from: Option<Duration>,
+        to: Option<Duration>,
+    ) -> Vec<Sample> {
+        self.with_channel(project, component, |samples| {
+            samples.range(samples.window(from, to)).collect()
+        })
+        .unwrap_or_default()
+    }
+
+    ///
+    /// Summarises the samples of a component between two monotonic times to
+    /// about `width` points, so long windows can be plotted. Open ends are
+    /// taken from the first and last sample.
+    ///
+    pub fn downsample(
+        &self,
+        project: u64,
+        component: &str,
+        from: Option<Duration>,
+        to: Option<Duration>,
+        width: usize,
+        downsampling: Downsampling,
+    ) -> Series {
+        self.with_channel(project, component, |samples| {
+            let range = samples.window(from, to);
+
+            match downsampling {
+                Downsampling::Buckets => {
+                    if range.is_empty() {
+                        return Series::Buckets(vec![]);
+                    }
+
+                    let first = samples.monotonic(range.start);
+                    let last = samples.monotonic(range.end - 1);
+
+                    Series::Buckets(downsample::buckets(
+                        samples,
+                        range,
+                        from.unwrap_or(first),
+                        to.unwrap_or(last),
+                        width,
+                    ))
+                }
+                Downsampling::Lttb => Series::Lttb(downsample::lttb(samples, range, width)),
+            }
+        })
+        .unwrap_or_else(|| match downsampling {
+            Downsampling::Buckets => Series::Buckets(vec![]),
+            Downsampling::Lttb => Series::Lttb(vec![]),
+        })
+    }
+
+    // Only the component itself stays locked while its samples are used
+    fn with_channel<T, F>(&self, project: u64, component: &str, f: F) -> Option<T>
+    where
+        F: FnOnce(&Samples) -> T,
+    {
+        let channel = self
+            .projects
+            .lock()
+            .unwrap()
+            .get(&project)?
+            .channels
+            .get(component)
+            .cloned()?;
+
+        let samples = channel.lock().unwrap();
+
+        Some(f(&samples))
+    }
+
+    pub fn latest(&self, project: u64, component: &str) -> Option<Sample> {
+        self.with_channel(project, component, |samples| samples.last())
+            .flatten()
+    }
+
+    pub fn components(&self, project: u64) -> Vec<String> {
+        let mut components = self
+            .projects
+            .lock()
+            .unwrap()
+            .get(&project)
+            .map_or(vec![], |p| p.channels.keys().cloned().collect::<Vec<String>>());
+
+        components.sort();
+
+        components
+    }
+}
+
+///
+/// Records the readouts of one device into every project attached to it.
+/// Held by the reader of the device, it remembers where each component goes
+/// so the store is only locked when a component first shows up or a project
+/// attaches or goes away.
+///
+pub struct DeviceTelemetry {
+    telemetry: Arc<Telemetry>,
+    device: u64,
+    /// Generation of the store the channels were looked up in
+    generation: Option<u64>,
+    channels: HashMap<String, Vec<Channel>>,
+}
+
+impl DeviceTelemetry {
+    pub fn record(&mut self, component: &str, value: f64, time: Timestamp) {
+        let generation = self.telemetry.generation.load(Ordering::Acquire);
+
+        if self.generation != Some(generation) {
+            self.generation = Some(generation);
+            self.channels.clear();
+        }
+
+        if !self.channels.contains_key(component) {
+            let channels = self.telemetry.channels(self.device, component, time);
+            self.channels.insert(component.to_string(), channels);
+        }
+
+        let limit = self.telemetry.limit();
+
+        for channel in &self.channels[component] {
+            channel.lock().unwrap().push(time, value, limit);
+        }
+    }
+}
+
+impl<R: tauri::Runtime> BuilderConfig<R> {
+    /// Keeps up to `capacity` samples per component of every project, in
+    /// about `budget` bytes all together
+    pub fn telemetry(self, capacity: usize, budget: usize) -> BuilderConfig<R> {
+        let telemetry = Arc::new(Telemetry::new(capacity, budget));
+
+        self.register_commands(
+            generate_handler![
+                routes::telemetry_range,
+                routes::telemetry_latest,
+                routes::telemetry_components,
+                routes::telemetry_downsample
+            ],
+            &[
+                "telemetry_range",
+                "telemetry_latest",
+                "telemetry_components",
+                "telemetry_downsample"
+            ],
+        )
+        .fold(|b| b.manage(telemetry))
+    }
+}
+
+mod routes {
+    use crate::err::{Error, ErrorKind};
+    use crate::telemetry::{Downsampling, Sample, Series, Telemetry};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tauri::State;
+
+    /// `from` and `to` are monotonic times in microseconds, like the
+    /// `monotonic` of a `Timestamp`
+    #[tauri::command]
+    pub fn telemetry_range(
+        project_id: u64,
+        component: String,
+        from: Option<u64>,
+        to: Option<u64>,
+        telemetry: State<'_, Arc<Telemetry>>,
+    ) -> Result<Vec<Sample>, Error> {
+        Ok(telemetry.range(
+            project_id,
+            &component,
+            from.map(Duration::from_micros),
+            to.map(Duration::from_micros),
+        ))
+    }
+
+    #[tauri::command]
+    pub fn telemetry_latest(
+        project_id: u64,
+        component: String,
+        telemetry: State<'_, Arc<Telemetry>>,
+    ) -> Result<Option<Sample>, Error> {
+        Ok(telemetry.latest(project_id, &component))
+    }
+
+    ///
+    /// A component's samples between `from` and `to`, summarised to about
+    /// `width` points, usually the width of the plot in pixels. Summarising
+    /// an hour of samples takes a while, so it runs on a blocking thread.
+    ///
+    #[tauri::command]
+    pub async fn telemetry_downsample(
+        project_id: u64,
+        component: String,
+        from: Option<u64>,
+        to: Option<u64>,
+        width: usize,
+        downsampling: Downsampling,
+        telemetry: State<'_, Arc<Telemetry>>,
+    ) -> Result<Series, Error> {
+        if width == 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidArgument,
+                "The width must be greater than 0.",
+            ));
+        }
+
+        let telemetry = Arc::clone(&telemetry);
+
+        Ok(tauri::async_runtime::spawn_blocking(move || {
+            telemetry.downsample(
+                project_id,
+                &component,
+                from.map(Duration::from_micros),
+                to.map(Duration::from_micros),
+                width,
+                downsampling,
+            )
+        })
+        .await?)
+    }
+
+    #[tauri::command]
+    pub fn telemetry_components(
+        project_id: u64,
+        telemetry: State<'_, Arc<Telemetry>>,
+    ) -> Result<Vec<String>, Error> {
+        Ok(telemetry.components(project_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Downsampling, Point, Series, Telemetry};
+    use crate::timestamp::Timestamp;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn at(millis: u64) -> Timestamp {
+        Timestamp {
+            monotonic: Duration::from_millis(millis),
+            unix: Duration::from_millis(1_700_000_000_000 + millis),
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer() {
+        let telemetry = Arc::new(Telemetry::new(3, usize::MAX));
+        telemetry.attach(1, Some(7));
+        telemetry.attach(2, None);
+
+        let mut seven = telemetry.device(7);
+        let mut eight = telemetry.device(8);
+
+        for (i, millis) in [10, 20, 30, 40].into_iter().enumerate() {
+            seven.record("rpm", i as f64, at(millis));
+        }
+        seven.record("temp", 25.0, at(25));
+        eight.record("rpm", 100.0, at(50));
+
+        // The oldest sample was dropped
+        let all = telemetry.range(1, "rpm", None, None);
+        assert_eq!(all.iter().map(|s| s.value).collect::<Vec<f64>>(), vec![1.0, 2.0, 3.0]);
+
+        let range = telemetry.range(
+            1,
+            "rpm",
+            Some(Duration::from_millis(20)),
+            Some(Duration::from_millis(35)),
+        );
+        assert_eq!(range.iter().map(|s| s.value).collect::<Vec<f64>>(), vec![1.0, 2.0]);
+
+        let backwards = telemetry.range(
+            1,
+            "rpm",
+            Some(Duration::from_millis(35)),
+            Some(Duration::from_millis(20)),
+        );
+        assert!(backwards.is_empty());
+
+        assert_eq!(telemetry.latest(1, "rpm").unwrap().time, at(40));
+        assert_eq!(telemetry.components(1), vec!["rpm", "temp"]);
+        assert!(telemetry.components(2).is_empty());
+
+        // History survives the device being swapped, not the project closing
+        telemetry.attach(1, Some(8));
+        seven.record("rpm", 200.0, at(60));
+        assert_eq!(telemetry.latest(1, "rpm").unwrap().value, 3.0);
+        eight.record("rpm", 300.0, at(70));
+        assert_eq!(telemetry.latest(1, "rpm").unwrap().value, 300.0);
+
+        telemetry.remove(1);
+        assert!(telemetry.latest(1, "rpm").is_none());
+    }
+
+    #[test]
+    fn test_downsample() {
+        let telemetry = Arc::new(Telemetry::new(1000, usize::MAX));
+        telemetry.attach(1, Some(7));
+
+        let mut seven = telemetry.device(7);
+
+        for millis in 0..1000 {
+            seven.record("rpm", (millis % 10) as f64, at(millis));
+        }
+
+        let Series::Buckets(buckets) = telemetry.downsample(
+            1,
+            "rpm",
+            Some(Duration::from_millis(500)),
+            None,
+            50,
+            Downsampling::Buckets,
+        ) else {
+            panic!("expected buckets");
+        };
+        assert_eq!(buckets.len(), 50);
+        assert_eq!(buckets[0].from, at(500));
+        assert_eq!((buckets[0].min, buckets[0].max, buckets[0].count), (0.0, 9.0, 10));
+
+        let Series::Lttb(line) = telemetry.downsample(1, "rpm", None, None, 100, Downsampling::Lttb)
+        else {
+            panic!("expected a line");
+        };
+        assert_eq!(line.len(), 100);
+
+        assert_eq!(
+            telemetry.downsample(1, "temp", None, None, 100, Downsampling::Lttb),
+            Series::Lttb(vec![])
+        );
+    }
+
+    #[test]
+    fn test_budget() {
+        // Room for 10 samples, shared by the components
+        let telemetry = Arc::new(Telemetry::new(8, 10 * size_of::<Point>()));
+        telemetry.attach(1, Some(7));
+
+        let mut seven = telemetry.device(7);
+
+        for millis in 0..20 {
+            seven.record("rpm", millis as f64, at(millis));
+        }
+        assert_eq!(telemetry.range(1, "rpm", None, None).len(), 8);
+
+        for millis in 0..20 {
+            seven.record("temp", millis as f64, at(millis));
+        }
+        seven.record("rpm", 20.0, at(20));
+
+        assert_eq!(telemetry.range(1, "rpm", None, None).len(), 5);
+        assert_eq!(telemetry.range(1, "temp", None, None).len(), 5);
+        assert_eq!(telemetry.latest(1, "temp").unwrap().time, at(19));
+
+        // A closed project gives its share back
+        telemetry.attach(2, Some(7));
+        seven.record("rpm", 21.0, at(21));
+        telemetry.remove(2);
+        assert_eq!(telemetry.limit(), 5);
+    }
+}
@@ -0,0 +1,64 @@
+use serde::{Serialize, Serializer};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+static EPOCH: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+pub fn unix_now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Steady time since the app started
+pub fn monotonic_now() -> Duration {
+    EPOCH.elapsed()
+}
+
+///
+/// When something happened on the host. `monotonic` never jumps and is what
+/// ordering and intervals should use, `unix` is the wall clock for display
+/// and export. Both are sent to the frontend in microseconds.
+///
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+pub struct Timestamp {
+    #[serde(serialize_with = "micros")]
+    pub monotonic: Duration,
+    #[serde(serialize_with = "micros")]
+    pub unix: Duration,
+}
+
+impl Timestamp {
+    pub fn now() -> Timestamp {
+        Timestamp {
+            monotonic: monotonic_now(),
+            unix: unix_now(),
+        }
+    }
+}
+
+fn micros<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_micros() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Timestamp;
+    use std::time::Duration;
+
+    #[test]
+    fn test_serialize_micros() {
+        let time = Timestamp {
+            monotonic: Duration::from_millis(1500),
+            unix: Duration::from_secs(1_700_000_000),
+        };
+
+        assert_eq!(
+            serde_json::to_value(time).unwrap(),
+            serde_json::json!({"monotonic": 1_500_000, "unix": 1_700_000_000_000_000u64})
+        );
+
+        let earlier = Timestamp::now();
+        assert!(Timestamp::now().monotonic >= earlier.monotonic);
+    }
+}
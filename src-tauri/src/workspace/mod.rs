@@ -1,3 +1,5 @@
+use crate::command::describe::DeviceDescription;
+use crate::command::schema::CommandSchema;
 use crate::config::BuilderConfig;
 use crate::drive::{Drive, Vehicle};
 use crate::err::Error;
@@ -43,16 +45,86 @@ pub struct CommandParameter {
     pub display_name: String,
     #[serde(rename = "type")]
     pub value_type: String,
+    /// Bounds of `number` and `integer` parameters
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Choices of an `enum` parameter
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub options: Vec<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub struct CommandDefinition {
-    id: u64,
-    name: String,
-    display_name: String,
-    icon: String,
-    parameters: Vec<CommandParameter>,
+    pub id: u64,
+    pub name: String,
+    pub display_name: String,
+    pub icon: String,
+    pub parameters: Vec<CommandParameter>,
+}
+
+impl Workspace {
+    /// Every command defined by the command panels of this workspace
+    pub fn command_schema(&self) -> CommandSchema {
+        CommandSchema::new(self.widgets.iter().flat_map(|w| match &w.behavior {
+            WidgetBehavior::CommandPanel { schema } => schema.iter(),
+            _ => [].iter(),
+        }))
+    }
+
+    ///
+    /// Merges what a device said about itself into the first command panel
+    /// and readout. Definitions the device also sends are updated but keep
+    /// their id and icon. Returns false if there was nothing to merge into.
+    ///
+    pub fn merge_description(&mut self, description: &DeviceDescription) -> bool {
+        let mut merged = false;
+
+        let schema = self.widgets.iter_mut().find_map(|w| match &mut w.behavior {
+            WidgetBehavior::CommandPanel { schema } => Some(schema),
+            _ => None,
+        });
+
+        if let Some(schema) = schema {
+            for definition in &description.commands {
+                match schema.iter_mut().find(|d| d.name == definition.name) {
+                    Some(existing) => {
+                        existing.display_name = definition.display_name.clone();
+                        existing.parameters = definition.parameters.clone();
+                    }
+                    None => {
+                        let id = schema.iter().map(|d| d.id + 1).max().unwrap_or(0);
+
+                        schema.push(CommandDefinition {
+                            id,
+                            ..definition.clone()
+                        });
+                    }
+                }
+            }
+
+            merged = true;
+        }
+
+        let components = self.widgets.iter_mut().find_map(|w| match &mut w.behavior {
+            WidgetBehavior::Readout { components } => Some(components),
+            _ => None,
+        });
+
+        if let Some(components) = components {
+            for readout in &description.readouts {
+                if !components.contains(readout) {
+                    components.push(readout.clone());
+                }
+            }
+
+            merged = true;
+        }
+
+        merged
+    }
 }
 
 impl WidgetBehavior {
@@ -118,6 +190,10 @@ impl WorkspaceHandler {
 
         Ok(())
     }
+
+    pub fn get(&self, id: &str) -> Option<&Workspace> {
+        self.workspaces.get(id)
+    }
 }
 
 struct WorkspaceDriver {
@@ -151,12 +227,14 @@ impl<R: tauri::Runtime> BuilderConfig<R> {
                 routes::workspace_push,
                 routes::workspace_ls,
                 routes::workspace_get,
+                routes::workspace_merge_description,
                 routes::open_workspace_folder
             ],
             &[
                 "workspace_push",
                 "workspace_ls",
                 "workspace_get",
+                "workspace_merge_description",
                 "open_workspace_folder",
             ],
         )
@@ -177,6 +255,10 @@ impl<R: tauri::Runtime> BuilderConfig<R> {
 }
 
 mod routes {
+    use crate::command::describe::DeviceDescription;
+    use crate::command::schema::CommandSchemas;
+    use crate::err::{Error, ErrorKind};
+    use crate::project::Projects;
     use crate::workspace::{Workspace, WorkspaceHandler};
     use opener::open;
     use std::path::PathBuf;
@@ -193,12 +275,24 @@ mod routes {
     }
 
     #[tauri::command]
-    pub fn workspace_push(workspace: Workspace, handler: State<'_, Arc<Mutex<WorkspaceHandler>>>) {
+    pub fn workspace_push(
+        workspace: Workspace,
+        handler: State<'_, Arc<Mutex<WorkspaceHandler>>>,
+        projects: State<'_, Projects>,
+        schemas: State<'_, Arc<CommandSchemas>>,
+    ) {
+        let id = workspace.id.clone();
+
         handler
             .lock()
             .unwrap()
             .workspaces
-            .insert(workspace.id.clone(), workspace);
+            .insert(id.clone(), workspace);
+
+        // Command panels may have changed
+        for project in projects.lock().unwrap().values().filter(|p| p.workspace() == id) {
+            project.attach_schema(&handler, &schemas);
+        }
     }
 
     #[tauri::command]
@@ -219,4 +313,91 @@ mod routes {
     ) -> Option<Workspace> {
         handler.lock().unwrap().workspaces.get(&id).cloned()
     }
+
+    ///
+    /// Merges a device description into a workspace and returns the result,
+    /// the frontend replaces its widgets with it
+    ///
+    #[tauri::command]
+    pub fn workspace_merge_description(
+        id: String,
+        description: DeviceDescription,
+        handler: State<'_, Arc<Mutex<WorkspaceHandler>>>,
+        projects: State<'_, Projects>,
+        schemas: State<'_, Arc<CommandSchemas>>,
+    ) -> Result<Workspace, Error> {
+        let mut guard = handler.lock().unwrap();
+        let workspace = guard
+            .workspaces
+            .get_mut(&id)
+            .ok_or_else(|| Error::new(ErrorKind::NoSuchWorkspace, "Cannot find this workspace."))?;
+
+        if !workspace.merge_description(&description) {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Add a command panel or readout to this workspace first.",
+            ));
+        }
+
+        let workspace = workspace.clone();
+        drop(guard);
+
+        for project in projects.lock().unwrap().values().filter(|p| p.workspace() == id) {
+            project.attach_schema(&handler, &schemas);
+        }
+
+        Ok(workspace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Workspace;
+    use crate::command::describe::DeviceDescription;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_description() {
+        let mut workspace: Workspace = serde_json::from_value(json!({
+            "id": "bench",
+            "widgets": [{
+                "type": "commandPanel",
+                "pos": {"x": 0, "y": 0, "width": 1, "height": 1},
+                "behavior": {"type": "commandPanel", "schema": [
+                    {"id": 4, "name": "motor", "displayName": "Old", "icon": "M", "parameters": []}
+                ]}
+            }]
+        }))
+        .unwrap();
+
+        let description: DeviceDescription = serde_json::from_value(json!({
+            "commands": [
+                {"id": 0, "name": "motor", "displayName": "Motor", "icon": "", "parameters": [
+                    {"index": 0, "displayName": "Speed", "type": "number"}
+                ]},
+                {"id": 1, "name": "ping", "displayName": "ping", "icon": "", "parameters": []}
+            ],
+            "readouts": ["rpm"]
+        }))
+        .unwrap();
+
+        assert!(workspace.merge_description(&description));
+
+        let schema = workspace.command_schema();
+        assert!(schema.defines("motor"));
+        assert!(schema.defines("ping"));
+
+        let json = serde_json::to_value(&workspace).unwrap();
+        let merged = &json["widgets"][0]["behavior"]["schema"];
+        assert_eq!(merged[0]["id"], 4);
+        assert_eq!(merged[0]["icon"], "M");
+        assert_eq!(merged[0]["displayName"], "Motor");
+        assert_eq!(merged[1]["id"], 5);
+
+        let mut empty = Workspace {
+            id: "empty".to_string(),
+            widgets: vec![],
+        };
+        assert!(!empty.merge_description(&description));
+    }
 }
@@ -1,11 +1,101 @@
 import {Channel, invoke} from "@tauri-apps/api/core";
 import {createContext} from "react";
+import {CommandDefinition} from "./widget/command_panel.tsx";
+import {LogEntry} from "./widget/log/common.ts";
 
 export type Command = {
     action: string,
     arguments: string[]
 }
 
+// A command checked against the workspace's command panels, arguments are
+// coerced to their parameter types (hex parameters become byte arrays)
+export type TypedCommand = {
+    action: string,
+    arguments: (string | number | boolean | number[])[]
+}
+
+// Host time a chunk was read at, in microseconds. `monotonic` counts from
+// app start and never jumps, `unix` is the wall clock.
+export type Timestamp = {
+    monotonic: number,
+    unix: number
+}
+
+// Modem input lines, true when asserted
+export type ControlLines = {
+    cts: boolean,
+    dsr: boolean,
+    cd: boolean,
+    ri: boolean
+}
+
+export type ParseError = {
+    message: string,
+    content: string
+}
+
+// What a device answered `[help --schema]` with
+export type DeviceDescription = {
+    commands: CommandDefinition[],
+    readouts: string[]
+}
+
+// How the reply to `Project.request` is recognised, by default the next
+// command with the request's own action
+export type ReplyMatch =
+    | { type: "action"; action?: string }
+    | { type: "sequence"; action?: string }
+    | { type: "pattern"; pattern: string }
+
+// A readout kept by the backend's telemetry store
+export type Sample = {
+    time: Timestamp,
+    value: number
+}
+
+// Samples read during a slice of a plot window, `from` and `to` are the
+// first and last of them
+export type Bucket = {
+    from: Timestamp,
+    to: Timestamp,
+    min: number,
+    max: number,
+    avg: number,
+    count: number
+}
+
+export type Downsampling = "buckets" | "lttb"
+
+export type Series =
+    | { type: "Buckets"; data: Bucket[] }
+    | { type: "Lttb"; data: Sample[] }
+
+export type FrameStats = {
+    frames: number,
+    invalid: number
+}
+
+export type PortDescriptor = {
+    name: string,
+    kind: "usb" | "pci" | "bluetooth" | "other",
+    vid: number | null,
+    pid: number | null,
+    serialNumber: string | null,
+    manufacturer: string | null,
+    product: string | null
+}
+
+export type PortEvent =
+    | { type: "PortAdded"; data: PortDescriptor }
+    | { type: "PortRemoved"; data: PortDescriptor };
+
+export function portLabel(port: PortDescriptor): string {
+    const details = [port.product, port.serialNumber].filter((it) => it)
+
+    return details.length ? `${port.name} (${details.join(", ")})` : port.name
+}
+
 export class ListenerRef {
 }
 
@@ -20,13 +110,20 @@ export type DeviceRef = {
 
 export type ListenerManager = {
     registerListener: {
-        command: (fn: (command: Command) => void) => ListenerRef,
-        raw: (fn: (content: Uint8Array) => void) => ListenerRef,
-        close: (fn: (error: boolean) => void) => void
+        command: (fn: (command: Command, time: Timestamp) => void) => ListenerRef,
+        raw: (fn: (content: Uint8Array, time: Timestamp) => void) => ListenerRef,
+        frame: (fn: (content: Uint8Array, time: Timestamp, valid: boolean) => void) => ListenerRef,
+        readout: (fn: (component: string, value: number, time: Timestamp) => void) => ListenerRef,
+        log: (fn: (entry: LogEntry, time: Timestamp) => void) => ListenerRef,
+        close: (fn: (error: boolean) => void) => void,
+        description: (fn: (description: DeviceDescription) => void) => void
     },
     unregisterListener: {
         command: (ref: ListenerRef) => void,
-        raw: (ref: ListenerRef) => void
+        raw: (ref: ListenerRef) => void,
+        frame: (ref: ListenerRef) => void,
+        readout: (ref: ListenerRef) => void,
+        log: (ref: ListenerRef) => void
     },
     push(event: DeviceEvent): void
     // moveListeners: (other: ListenerManager) => void
@@ -39,6 +136,12 @@ export type Project = {
     write: (string?: string | undefined, raw?: Uint8Array | undefined) => Promise<void>,
     close: () => Promise<void>,
     pushDevice: (device: Device) => Promise<void>,
+    request: (command: Command, reply?: ReplyMatch, timeout?: number, retries?: number) => Promise<Command>,
+    // `from` and `to` are monotonic times in microseconds, like `Timestamp.monotonic`
+    telemetryRange: (component: string, from?: number, to?: number) => Promise<Sample[]>,
+    telemetryLatest: (component: string) => Promise<Sample | null>,
+    telemetryComponents: () => Promise<string[]>,
+    telemetryDownsample: (component: string, width: number, downsampling: Downsampling, from?: number, to?: number) => Promise<Series>,
     manager: ProjectManager,
 } & ListenerManager
 
@@ -56,7 +159,7 @@ export type ProjectManager = {
     getDevice: (sort: string, name: string) => Device | null,
     openDevice: (sort: string, config: DeviceConfig) => Promise<Device>,
     deviceClosed: (device: Device) => void,
-    openProject: (device: Device) => Promise<Project>
+    openProject: (device: Device, workspace: string) => Promise<Project>
 }
 
 export class ProjectManagerImpl implements ProjectManager {
@@ -107,9 +210,9 @@ export class ProjectManagerImpl implements ProjectManager {
         device.open = false
     };
 
-    async openProject(d: Device): Promise<Project> {
+    async openProject(d: Device, workspace: string): Promise<Project> {
         let id = await invoke<number>("new_project", {
-            workspace: "idk", // TODO not actually used,
+            workspace: workspace,
             reference: d.ref
         });
 
@@ -128,19 +231,38 @@ export class ProjectManagerImpl implements ProjectManager {
 }
 
 export type DeviceEvent =
-    | { type: "RecRaw"; data: Array<number> }
-    | { type: "RecCommand"; data: Command }
+    | { type: "RecRaw"; data: { content: Array<number>, time: Timestamp } }
+    | { type: "RecFrame"; data: { content: Array<number>, time: Timestamp, valid: boolean } }
+    | { type: "RecCommand"; data: { command: Command, typed: TypedCommand | null, time: Timestamp } }
+    | { type: "ParseError"; data: { error: ParseError, time: Timestamp } }
+    | { type: "Readout"; data: { component: string, value: number, ts: Timestamp } }
+    | { type: "Log"; data: { entry: LogEntry, time: Timestamp } }
+    | { type: "Description"; data: DeviceDescription }
+    | { type: "Address"; data: string }
+    | { type: "Reconnecting"; data: { attempt: number } }
+    | { type: "Reconnected" }
+    | { type: "ControlLines"; data: ControlLines }
+    | { type: "FrameStats"; data: FrameStats }
+    | { type: "Error"; data: { message: string, time: Timestamp } }
     | { type: "Close", data: {error: boolean} };
 
 class ListenerManagerImpl implements ListenerManager {
-    private commandListeners: Map<ListenerRef, (command: Command) => void> = new Map()
-    private rawListeners: Map<ListenerRef, ((content: Uint8Array) => void)> = new Map()
+    private commandListeners: Map<ListenerRef, (command: Command, time: Timestamp) => void> = new Map()
+    private rawListeners: Map<ListenerRef, ((content: Uint8Array, time: Timestamp) => void)> = new Map()
+    private frameListeners: Map<ListenerRef, ((content: Uint8Array, time: Timestamp, valid: boolean) => void)> = new Map()
+    private readoutListeners: Map<ListenerRef, ((component: string, value: number, time: Timestamp) => void)> = new Map()
+    private logListeners: Map<ListenerRef, ((entry: LogEntry, time: Timestamp) => void)> = new Map()
     private closeListeners: ((error: boolean) => void)[] = []
+    private descriptionListeners: ((description: DeviceDescription) => void)[] = []
 
     registerListener: {
-        command: (fn: (command: Command) => void) => ListenerRef;
-        raw: (fn: (content: Uint8Array) => void) => ListenerRef,
-        close: (fn: (error: boolean) => void) => void
+        command: (fn: (command: Command, time: Timestamp) => void) => ListenerRef;
+        raw: (fn: (content: Uint8Array, time: Timestamp) => void) => ListenerRef,
+        frame: (fn: (content: Uint8Array, time: Timestamp, valid: boolean) => void) => ListenerRef,
+        readout: (fn: (component: string, value: number, time: Timestamp) => void) => ListenerRef,
+        log: (fn: (entry: LogEntry, time: Timestamp) => void) => ListenerRef,
+        close: (fn: (error: boolean) => void) => void,
+        description: (fn: (description: DeviceDescription) => void) => void
     } = {
         command: (cb) => {
             const ref = new ListenerRef()
@@ -152,30 +274,76 @@ class ListenerManagerImpl implements ListenerManager {
             this.rawListeners.set(ref, cb)
             return ref
         },
+        frame: (cb) => {
+            const ref = new ListenerRef()
+            this.frameListeners.set(ref, cb)
+            return ref
+        },
+        readout: (cb) => {
+            const ref = new ListenerRef()
+            this.readoutListeners.set(ref, cb)
+            return ref
+        },
+        log: (cb) => {
+            const ref = new ListenerRef()
+            this.logListeners.set(ref, cb)
+            return ref
+        },
         close: (fn) => {
             this.closeListeners.push(fn)
+        },
+        description: (fn) => {
+            this.descriptionListeners.push(fn)
         }
     }
 
     unregisterListener: {
         command: (ref: ListenerRef) => void;
-        raw: (ref: ListenerRef) => void
+        raw: (ref: ListenerRef) => void,
+        frame: (ref: ListenerRef) => void,
+        readout: (ref: ListenerRef) => void,
+        log: (ref: ListenerRef) => void
     } = {
         command: (ref: ListenerRef) => {
             this.commandListeners.delete(ref)
         },
         raw: (ref: ListenerRef) => {
             this.rawListeners.delete(ref)
+        },
+        frame: (ref: ListenerRef) => {
+            this.frameListeners.delete(ref)
+        },
+        readout: (ref: ListenerRef) => {
+            this.readoutListeners.delete(ref)
+        },
+        log: (ref: ListenerRef) => {
+            this.logListeners.delete(ref)
         }
     }
 
     push(e: DeviceEvent): void {
         if (e.type === "RecRaw") {
             for (let [_, listener] of this.rawListeners) {
-                listener(Uint8Array.from(e.data))
+                listener(Uint8Array.from(e.data.content), e.data.time)
+            }
+        } else if (e.type === "RecFrame") {
+            for (let [_, listener] of this.frameListeners) {
+                listener(Uint8Array.from(e.data.content), e.data.time, e.data.valid)
             }
         } else if (e.type === "RecCommand") {
             for (let [_, listener] of this.commandListeners) {
+                listener(e.data.command, e.data.time)
+            }
+        } else if (e.type === "Readout") {
+            for (let [_, listener] of this.readoutListeners) {
+                listener(e.data.component, e.data.value, e.data.ts)
+            }
+        } else if (e.type === "Log") {
+            for (let [_, listener] of this.logListeners) {
+                listener(e.data.entry, e.data.time)
+            }
+        } else if (e.type === "Description") {
+            for (let listener of this.descriptionListeners) {
                 listener(e.data)
             }
         } else if (e.type === "Close") {
@@ -210,21 +378,35 @@ class ProjectImpl implements Project {
     manager: ProjectManager;
 
     registerListener: {
-        command: (fn: (command: Command) => void) => ListenerRef;
-        raw: (fn: (content: Uint8Array) => void) => ListenerRef,
-        close: (fn: (error: boolean) => void) => void
+        command: (fn: (command: Command, time: Timestamp) => void) => ListenerRef;
+        raw: (fn: (content: Uint8Array, time: Timestamp) => void) => ListenerRef,
+        frame: (fn: (content: Uint8Array, time: Timestamp, valid: boolean) => void) => ListenerRef,
+        readout: (fn: (component: string, value: number, time: Timestamp) => void) => ListenerRef,
+        log: (fn: (entry: LogEntry, time: Timestamp) => void) => ListenerRef,
+        close: (fn: (error: boolean) => void) => void,
+        description: (fn: (description: DeviceDescription) => void) => void
     } = {
         command: (fn) => this.listenerManager.registerListener.command(fn),
         raw: (fn) => this.listenerManager.registerListener.raw(fn),
+        frame: (fn) => this.listenerManager.registerListener.frame(fn),
+        readout: (fn) => this.listenerManager.registerListener.readout(fn),
+        log: (fn) => this.listenerManager.registerListener.log(fn),
         close: (fn) => this.listenerManager.registerListener.close(fn),
+        description: (fn) => this.listenerManager.registerListener.description(fn),
     }
 
     unregisterListener: {
         command: (ref: ListenerRef) => void;
-        raw: (ref: ListenerRef) => void
+        raw: (ref: ListenerRef) => void,
+        frame: (ref: ListenerRef) => void,
+        readout: (ref: ListenerRef) => void,
+        log: (ref: ListenerRef) => void
     } = {
         command: (fn) => this.listenerManager.unregisterListener.command(fn),
         raw: (fn) => this.listenerManager.unregisterListener.raw(fn),
+        frame: (fn) => this.listenerManager.unregisterListener.frame(fn),
+        readout: (fn) => this.listenerManager.unregisterListener.readout(fn),
+        log: (fn) => this.listenerManager.unregisterListener.log(fn),
     }
 
     write: (
@@ -235,17 +417,71 @@ class ProjectImpl implements Project {
 
         if (!buf) return
 
-        await invoke("device_write", {
+        let time = await invoke<Timestamp>("device_write", {
             projectId: this.id,
             buf: buf
         })
 
         this.push({
             type: "RecRaw",
-            data: Array.from(buf)
+            data: {content: Array.from(buf), time: time}
         })
     };
 
+    request(
+        command: Command,
+        reply?: ReplyMatch,
+        timeout?: number,
+        retries?: number
+    ): Promise<Command> {
+        return invoke<Command>("device_request", {
+            projectId: this.id,
+            command: command,
+            reply: reply,
+            timeout: timeout,
+            retries: retries
+        })
+    }
+
+    telemetryRange(component: string, from?: number, to?: number): Promise<Sample[]> {
+        return invoke<Sample[]>("telemetry_range", {
+            projectId: this.id,
+            component: component,
+            from: from,
+            to: to
+        })
+    }
+
+    telemetryLatest(component: string): Promise<Sample | null> {
+        return invoke<Sample | null>("telemetry_latest", {
+            projectId: this.id,
+            component: component
+        })
+    }
+
+    telemetryComponents(): Promise<string[]> {
+        return invoke<string[]>("telemetry_components", {
+            projectId: this.id
+        })
+    }
+
+    telemetryDownsample(
+        component: string,
+        width: number,
+        downsampling: Downsampling,
+        from?: number,
+        to?: number
+    ): Promise<Series> {
+        return invoke<Series>("telemetry_downsample", {
+            projectId: this.id,
+            component: component,
+            from: from,
+            to: to,
+            width: width,
+            downsampling: downsampling
+        })
+    }
+
     close(): Promise<void> {
         return invoke("close_project", {
             projectId: this.id,
@@ -1,10 +1,10 @@
 import React, {ReactNode, useContext, useEffect, useState} from 'react';
-import {invoke} from "@tauri-apps/api/core";
+import {Channel, invoke} from "@tauri-apps/api/core";
 import {Dropdown, DropdownItem} from "../component/dropdown";
 import Input from "../component/input";
 import Button from "../component/button";
 import {SessionWindow} from "../session_manager";
-import {DeviceConfig, Projects} from "../device";
+import {DeviceConfig, PortDescriptor, PortEvent, portLabel, Projects} from "../device";
 import {useAlerts} from "../alert";
 import Workspace from "./workspace/page.tsx";
 import Home from "./home/home.tsx";
@@ -86,7 +86,41 @@ type ConfigProps = {
     disabled: boolean
 }
 
+type BaudScore = {
+    baudRate: number,
+    score: number,
+    bytes: number,
+    printable: number,
+    lines: number,
+    commands: number
+}
+
 const SerialConfigPanel: React.FC<ConfigProps> = ({config, setConfig, disabled}) => {
+    const [detecting, setDetecting] = useState<boolean>(false);
+
+    // Listens at each candidate rate and pre-fills the most plausible one
+    const detectBaudRate = async () => {
+        if (!config.name) return
+
+        setDetecting(true)
+        try {
+            const ranked = await invoke<BaudScore[]>("detect_baud", {
+                port: config.name,
+                dataBits: config.data_bits,
+                parity: config.parity,
+                stopBits: config.stop_bits,
+            })
+
+            if (ranked.length && ranked[0].score > 0) {
+                setConfig((c: DeviceConfig) => ({...c, baud_rate: ranked[0].baudRate}))
+            }
+        } catch (e) {
+            console.log(e)
+        } finally {
+            setDetecting(false)
+        }
+    }
+
     useEffect(() => {
         // Only set defaults if not disabled (i.e., not loading an existing device)
         if (!disabled) {
@@ -105,9 +139,9 @@ const SerialConfigPanel: React.FC<ConfigProps> = ({config, setConfig, disabled})
         <div className="space-y-4 w-full">
             <div className={"flex justify-between items-center w-full"}>
                 <p className="font-medium ">Baud rate:</p>
-                <div className="w-48">
+                <div className="w-48 flex flex-row justify-between items-center gap-2">
                     <Input
-                        disabled={disabled}
+                        disabled={disabled || detecting}
                         onChange={(it) => {
                             const value = it.target.value
                             setConfig({
@@ -117,6 +151,9 @@ const SerialConfigPanel: React.FC<ConfigProps> = ({config, setConfig, disabled})
                         }}
                         value={config.baud_rate || 115200}
                     />
+                    <Button disabled={disabled || detecting || !config.name} onClick={detectBaudRate}>
+                        {detecting ? "..." : "Detect"}
+                    </Button>
                 </div>
             </div>
             <div className={"flex justify-between items-center w-full"}>
@@ -186,7 +223,7 @@ const ConfigureProject: React.FC<{ workspace: string } & SessionWindow> = ({work
     // State initialization with explicit types
     const alerts = useAlerts();
 
-    const [availableDevices, setAvailableDevices] = useState<string[]>([]);
+    const [availableDevices, setAvailableDevices] = useState<PortDescriptor[]>([]);
     const projectManager = useContext(Projects)
 
     const [config, setConfig] = useState<DeviceConfig>({});
@@ -199,13 +236,27 @@ const ConfigureProject: React.FC<{ workspace: string } & SessionWindow> = ({work
     }, [])
 
     useEffect(() => {
-        invoke("device_available", {
-            sort: connectionSort
-        }).then((it) => {
-            setAvailableDevices(it as string[]);
-        }).catch((e) => {
+        setAvailableDevices([])
+
+        // The watcher reports every present port first, then hotplug changes
+        const channel = new Channel<PortEvent>()
+        channel.onmessage = (event) => {
+            setAvailableDevices((ports) => event.type === "PortAdded"
+                ? [...ports, event.data]
+                : ports.filter((port) => port.name !== event.data.name))
+        }
+
+        const watch = invoke<number>("watch_ports", {
+            sort: connectionSort,
+            channel: channel
+        })
+        watch.catch((e) => {
             console.log(e.toString())
         })
+
+        return () => {
+            watch.then((id) => invoke("unwatch_ports", {id: id})).catch(() => {})
+        }
     }, [connectionSort]);
 
     const handleConnectionTypeChange = (type: 'serial' | 'mock' | 'wifi') => {
@@ -250,7 +301,7 @@ const ConfigureProject: React.FC<{ workspace: string } & SessionWindow> = ({work
             connectionSort, finalConfig
         )
 
-        let project = await projectManager.openProject(device)
+        let project = await projectManager.openProject(device, workspace)
 
         setPage(Workspace, {
             id: workspace,
@@ -318,8 +369,8 @@ const ConfigureProject: React.FC<{ workspace: string } & SessionWindow> = ({work
                         <div className={"flex justify-between items-center w-full"}>
                             <p className="font-medium ">Device:</p>
                             <Dropdown className={"min-w-48"} onSelect={handleDeviceChange} value={config.name}>
-                                {availableDevices.map((id) => {
-                                    return <DropdownItem key={id} value={id}>{id}</DropdownItem>
+                                {availableDevices.map((port) => {
+                                    return <DropdownItem key={port.name} value={port.name}>{portLabel(port)}</DropdownItem>
                                 })}
                             </Dropdown>
                         </div>
@@ -1,7 +1,7 @@
 import React, {useEffect, useLayoutEffect, useRef, useState} from "react";
 import {useAlerts} from "../../alert.tsx";
 import {toolRegistry} from "../../main.tsx";
-import {Project} from "../../device.tsx";
+import {DeviceDescription, Project} from "../../device.tsx";
 import {SessionWindow} from "../../session_manager.tsx";
 import {BoundingBox, Widget} from "../../widget/widget.ts";
 import {ActiveDrag, ActiveResize, DragTarget, ResizeBorder, WidgetRegion} from "./types.ts";
@@ -38,6 +38,9 @@ const Workspace: React.FC<{ id: string, project: Project } & SessionWindow> = ({
     const modified = useRef<Widget<any>[]>([]);
     const alerts = useAlerts();
 
+    // A device description waiting for the user to merge it
+    const [description, setDescription] = useState<DeviceDescription | null>(null);
+
     const [configurationState, setConfigurationState] = useState<ConfigurationState>({
         state: null
     });
@@ -97,9 +100,42 @@ const Workspace: React.FC<{ id: string, project: Project } & SessionWindow> = ({
             }
         })
 
+        project.registerListener.description((description) => {
+            setDescription(description)
+        })
+
         return () => clearInterval(cb);
     }, [])
 
+    const toRegions = (widgets: Widget<any>[]): WidgetRegion[] => {
+        let id = 0
+        return widgets.map((w) => ({
+            widget: w,
+            id: `widget-${id++}`,
+        }));
+    }
+
+    const handleMergeDescription = () => {
+        if (!description) return
+
+        // Push pending edits first so the merge does not undo them
+        invoke("workspace_push", {
+            workspace: {id: id, widgets: widgets.map((it) => it.widget)}
+        }).then(() => invoke("workspace_merge_description", {
+            id: id,
+            description: description
+        })).then((result) => {
+            const layout = result as { id: string, widgets: Widget<any>[] }
+
+            setWidgets(toRegions(layout.widgets))
+            alerts.showAlert("info", "Merged the device description.")
+        }).catch((e: BackendError) => {
+            alerts.showAlert("warning", e.message)
+        })
+
+        setDescription(null)
+    }
+
     useEffect(() => {
         modified.current = widgets.map((it) => it.widget)
     }, [widgets]);
@@ -114,13 +150,7 @@ const Workspace: React.FC<{ id: string, project: Project } & SessionWindow> = ({
         }).then((result) => {
             const layout = result as { id: string, widgets: Widget<any>[] }
 
-            let id = 0
-            let regions = layout.widgets.map((w) => ({
-                widget: w,
-                id: `widget-${id++}`,
-            }));
-
-            setWidgets(regions);
+            setWidgets(toRegions(layout.widgets));
         })
 
         onClose(async () => {
@@ -269,6 +299,14 @@ const Workspace: React.FC<{ id: string, project: Project } & SessionWindow> = ({
     }
 
     return <div className={"h-full p-0.5 bg-gray-100"}>
+        {description && <div
+            className={"absolute top-2 left-1/2 -translate-x-1/2 z-[200] flex items-center gap-3 px-4 py-2 bg-white border border-gray-300 rounded-md shadow"}>
+            <span className={"text-sm text-gray-700"}>
+                The device describes {description.commands.length} commands and {description.readouts.length} readouts.
+            </span>
+            <button className={"text-sm text-blue-600"} onClick={handleMergeDescription}>Merge</button>
+            <button className={"text-sm text-gray-500"} onClick={() => setDescription(null)}>Dismiss</button>
+        </div>}
         {!!configurationState.state && <WidgetConfigurationPopup
             project={project}
             open={!!configurationState.state}
@@ -4,12 +4,17 @@ import {Project} from "../device.tsx";
 import {useAlerts} from "../alert.tsx";
 import {Dropdown, DropdownItem, DropdownItemProps} from "../component/dropdown.tsx";
 
-export type CommandType = 'string' | 'number'
+export type CommandType = 'string' | 'number' | 'integer' | 'boolean' | 'enum' | 'hex'
 
 export type CommandParameterDefinition = {
     index: number;
     displayName: string;
     type: CommandType;
+    // Bounds of number and integer parameters
+    min?: number;
+    max?: number;
+    // Choices of an enum parameter
+    options?: string[];
 };
 
 export type CommandDefinition = {
@@ -364,6 +369,35 @@ const ConfigInput: React.FC<{ label: string, value: string, onChange: (val: stri
     </div>
 );
 
+// Keeps what is typed, e.g. a lone "-", while passing on what it parses to
+const ConfigNumberInput: React.FC<{
+    label: string,
+    value: number | undefined,
+    onChange: (val: number | undefined) => void
+}> = ({label, value, onChange}) => {
+    const [text, setText] = useState(value?.toString() ?? "");
+
+    return <ConfigInput label={label} value={text} onChange={val => {
+        setText(val);
+        const number = parseFloat(val);
+        // An empty bound is no bound
+        onChange(isNaN(number) ? undefined : number);
+    }}/>
+};
+
+const ConfigListInput: React.FC<{
+    label: string,
+    value: string[],
+    onChange: (val: string[]) => void
+}> = ({label, value, onChange}) => {
+    const [text, setText] = useState(value.join(", "));
+
+    return <ConfigInput label={label} value={text} onChange={val => {
+        setText(val);
+        onChange(val.split(",").map(it => it.trim()).filter(it => it.length > 0));
+    }}/>
+};
+
 const ConfigSelect: React.FC<{
     label: string,
     value: string,
@@ -409,6 +443,13 @@ const CommandDefinitionConfiguration: React.FC<{
         });
     }
 
+    const handleParamChange = (index: number, changes: Partial<CommandParameterDefinition>) => {
+        setDefinition({
+            ...definition,
+            parameters: definition.parameters.map(p => p.index == index ? {...p, ...changes} : p),
+        });
+    }
+
     const handleRemoveParam = (index: number) => {
         setDefinition({
             ...definition,
@@ -486,7 +527,26 @@ const CommandDefinitionConfiguration: React.FC<{
                                               })}>
                                     <DropdownItem value="string">String</DropdownItem>
                                     <DropdownItem value="number">Number</DropdownItem>
+                                    <DropdownItem value="integer">Integer</DropdownItem>
+                                    <DropdownItem value="boolean">Boolean</DropdownItem>
+                                    <DropdownItem value="enum">Enum</DropdownItem>
+                                    <DropdownItem value="hex">Hex bytes</DropdownItem>
                                 </ConfigSelect>
+                                {(param.type === "number" || param.type === "integer") && <>
+                                    <ConfigNumberInput label="Minimum" value={param.min}
+                                                       onChange={val => handleParamChange(param.index, {
+                                                           min: val,
+                                                       })}/>
+                                    <ConfigNumberInput label="Maximum" value={param.max}
+                                                       onChange={val => handleParamChange(param.index, {
+                                                           max: val,
+                                                       })}/>
+                                </>}
+                                {param.type === "enum" &&
+                                    <ConfigListInput label="Options (comma separated)" value={param.options ?? []}
+                                                     onChange={val => handleParamChange(param.index, {
+                                                         options: val,
+                                                     })}/>}
                             </div>
                         ))
                     )}
@@ -602,6 +662,7 @@ const CommandSenderConfiguration: React.FC<{
                     </div>
                 ) : (
                     <CommandDefinitionConfiguration
+                        key={selectedCommand.id}
                         definition={selectedCommand}
                         setDefinition={(def) => {
                             setBehavior({
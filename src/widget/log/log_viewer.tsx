@@ -1,7 +1,7 @@
 import {Project} from "../../device.tsx";
 import {SetBehavior, ToolContainerProps, WidgetBehavior, WidgetHandler} from "../widget.ts";
 import React, {useCallback, useEffect, useRef, useState} from "react";
-import {LogEntry, parseLogContent} from "./common.ts";
+import {LogEntry} from "./common.ts";
 import {List, RowComponentProps, useDynamicRowHeight, useListRef} from "react-window";
 import {useAlerts} from "../../alert.tsx";
 import Button from "../../component/button.tsx";
@@ -52,7 +52,6 @@ function getLogLevelClasses(level: string) {
  * This is the main widget component that displays the logs.
  */
 const Widget: React.FC<{ project: Project, behavior: WidgetBehavior<"logs"> }> = ({project, behavior}) => {
-    const rawBuffer = useRef<string>("")
     const logs = useRef<LogEntry[]>([])
     const [displayLogs, setDisplayLogs] = useState<LogEntry[]>([]);
     const [selectedLog, setSelectedLog] = useState<LogEntry | null>(null);
@@ -62,27 +61,20 @@ const Widget: React.FC<{ project: Project, behavior: WidgetBehavior<"logs"> }> =
         defaultRowHeight: 10
     });
 
-    const handleReceive = useCallback((buf: Uint8Array) => {
-        const decoded = new TextDecoder().decode(buf);
-        const full = rawBuffer.current + decoded;
+    const handleLog = useCallback((entry: LogEntry) => {
+        if (!behavior.filter.includes(entry.level.toLowerCase())) return
 
-        const result = parseLogContent(full);
-        const logsResult = result.logs.filter((e => behavior.filter.includes(e.level.toLowerCase())))
-
-        if (logsResult.length > 0) {
-            const newLogs = [...logs.current, ...logsResult];
-            if (newLogs.length > MAX_LOG_LINES) {
-                logs.current = newLogs.slice(newLogs.length - MAX_LOG_LINES);
-            } else {
-                logs.current = newLogs;
-            }
+        const newLogs = [...logs.current, entry];
+        if (newLogs.length > MAX_LOG_LINES) {
+            logs.current = newLogs.slice(newLogs.length - MAX_LOG_LINES);
+        } else {
+            logs.current = newLogs;
         }
-        rawBuffer.current = full.substring(result.lastIndex);
     }, [behavior]);
 
     useEffect(() => {
-        const raw = project.registerListener.raw((buf) => {
-            handleReceive(buf);
+        const log = project.registerListener.log((entry) => {
+            handleLog(entry);
         });
 
         let animationHandle: number;
@@ -95,10 +87,10 @@ const Widget: React.FC<{ project: Project, behavior: WidgetBehavior<"logs"> }> =
         animationHandle = requestAnimationFrame(onAnimate);
 
         return () => {
-            project.unregisterListener.raw(raw)
+            project.unregisterListener.log(log)
             cancelAnimationFrame(animationHandle)
         }
-    }, [project, handleReceive, displayLogs]);
+    }, [project, handleLog, displayLogs]);
 
     useEffect(() => {
         if (autoScroll && scrollContainerRef.current && displayLogs.length > 0) {
@@ -221,15 +213,19 @@ const DetailPanel: React.FC<{ log: LogEntry, onClose: () => void }> = ({log, onC
             </div>
             <div>
                 <span className="font-semibold text-gray-500 w-24 inline-block">Timestamp:</span>
-                <span className="text-gray-800">Ms since start: {log.timestamp}</span>
+                <span className="text-gray-800">Ms since start: {log.timestamp ?? "-"}</span>
             </div>
+            {log.module && <div>
+                <span className="font-semibold text-gray-500 w-24 inline-block">Module:</span>
+                <span className="text-purple-600">{log.module}</span>
+            </div>}
             <div>
                 <span className="font-semibold text-gray-500 w-24 inline-block">File:</span>
-                <span className="text-purple-600" title={log.file}>{log.file}</span>
+                <span className="text-purple-600" title={log.file ?? undefined}>{log.file ?? "-"}</span>
             </div>
             <div>
                 <span className="font-semibold text-gray-500 w-24 inline-block">Line:</span>
-                <span className="text-cyan-600">{log.line}</span>
+                <span className="text-cyan-600">{log.line ?? "-"}</span>
             </div>
 
             <h4 className="font-semibold text-gray-500 pt-3 border-t border-gray-200/50 mt-3">Message:</h4>
@@ -269,43 +265,21 @@ const LogConfiguration: React.FC<{
     setBehavior: SetBehavior<"logs">,
     project: Project,
 }> = ({behavior, setBehavior, project}) => {
-    const rawBuffer = useRef<string>("")
-
     const [collectedLevels, setCollectedLevels] = useState<string[]>([])
 
     const [newFilter, setNewFilter] = useState<string>('');
     const alerts = useAlerts()
 
-    const handleReceive = (buf: Uint8Array) => {
-        const decoded = new TextDecoder().decode(buf);
-        const full = rawBuffer.current + decoded;
-
-        const result = parseLogContent(full);
-
-        setCollectedLevels((prev) => {
-            const maybeNew = result.logs.map((it) => it.level.toLowerCase())
-
-            for (let level of maybeNew) {
-                if (!prev.includes(level)) {
-                    return [...new Set([...maybeNew, ...prev])]
-                }
-            }
-
-            return prev
-        })
-
-        rawBuffer.current = full.substring(result.lastIndex);
-    }
-
     useEffect(() => {
-        const raw = project.registerListener.raw((buf) => {
-            handleReceive(buf);
+        const log = project.registerListener.log((entry) => {
+            const level = entry.level.toLowerCase()
+            setCollectedLevels((prev) => prev.includes(level) ? prev : [...prev, level])
         });
 
         return () => {
-            project.unregisterListener.raw(raw)
+            project.unregisterListener.log(log)
         }
-    }, [project, handleReceive]);
+    }, [project]);
 
     // Function to add a component, memoized with useCallback
     const handleAddFilter = useCallback(() => {
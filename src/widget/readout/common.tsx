@@ -1,111 +1,33 @@
 import {SetBehavior, WidgetBehavior} from "../widget.ts";
-import React, {useCallback, useEffect, useRef, useState} from "react";
+import React, {useCallback, useEffect, useState} from "react";
 import Button from "../../component/button.tsx";
 import {useAlerts} from "../../alert.tsx";
 import {Project} from "../../device.tsx";
 import {Autocomplete} from "../../component/autocomplete.tsx";
 
-export const READOUT_REGEX = /(\S+) = (\d+\.?\d*)/g;
-
-/**
- * The result of the parsing operation.
- */
-export interface Readout {
-    component: string;
-    value: number;
-}
-
-export interface ReadoutParseResult {
-    values: Readout[];
-    lastMatchEnd: number;
-}
-
-/**
- * Parses a string for "component$=value" readouts without using regex.
- *
- * @param fullString The complete string to parse.
- * @param validComponents An array of component names to look for.
- * @returns A ReadoutParseResult object with the found readouts and the end index.
- */
-export function parseReadouts(
-    fullString: string
-): ReadoutParseResult {
-    const readouts: Readout[] = [];
-    let lastMatchEnd = 0;
-
-    // We must use the 'g' (global) flag on the regex for repeated matching
-    // and use fullString.matchAll() to iterate over all matches.
-    const matches = fullString.matchAll(READOUT_REGEX);
-
-    for (const match of matches) {
-        const component = match[1];
-        const valueStr = match[2];
-
-        readouts.push({
-            component: component,
-            // The regex ensures valueStr is a valid number string
-            value: Number.parseFloat(valueStr),
-        });
-
-        lastMatchEnd = match.index! + match[0].length;
-    }
-
-    return {values: readouts, lastMatchEnd};
-}
-
-export function parseKnownReadouts(
-    fullString: string,
-    validComponents: string[]
-): ReadoutParseResult {
-    let result = parseReadouts(fullString);
-
-    return {
-        lastMatchEnd:result.lastMatchEnd,
-        values: result.values.filter((it) => validComponents.includes(it.component))
-    }
-}
-
 export const ReadoutConfiguration: React.FC<{
     behavior: WidgetBehavior<"readout"> | null,
     setBehavior: SetBehavior<"readout">,
     project: Project
 }> = ({behavior, setBehavior, project}) => {
-    const rawBuffer = useRef<string>("")
     const [collectedComponents, setCollectedComponents] = useState<string[]>([]);
 
     const [newComponent, setNewComponent] = useState<string>('');
     const alerts = useAlerts()
 
-    const handleReceive = (buf: Uint8Array) => {
-        const decoded = new TextDecoder().decode(buf);
-        const full = rawBuffer.current + decoded;
-
-        const result = parseReadouts(full);
-
-        setCollectedComponents((prev) => {
-            const maybeNew = result.values.map((it) => it.component)
-
-            for (let component of maybeNew) {
-                if (!prev.includes(component)) {
-                    return [...new Set([...maybeNew, ...prev])]
-                }
-            }
-
-            return prev
+    useEffect(() => {
+        project.telemetryComponents().then((components) => {
+            setCollectedComponents((prev) => [...new Set([...prev, ...components])])
         })
 
-        rawBuffer.current = full.substring(result.lastMatchEnd);
-    }
-
-    useEffect(() => {
-        const raw = project.registerListener.raw((buf) => {
-            handleReceive(buf);
+        const readout = project.registerListener.readout((component) => {
+            setCollectedComponents((prev) => prev.includes(component) ? prev : [...prev, component])
         });
 
         return () => {
-            project.unregisterListener.raw(raw)
+            project.unregisterListener.readout(readout)
         }
-    }, [project, handleReceive]);
+    }, [project]);
 
     // Function to add a component, memoized with useCallback
     const handleAddComponent = useCallback(() => {
@@ -1,7 +1,7 @@
 import React, {useCallback, useEffect, useRef, useState} from "react";
 import {ToolContainerProps, WidgetBehavior, WidgetHandler} from "../widget.ts";
 import {Project} from "../../device.tsx";
-import {parseKnownReadouts, ReadoutConfiguration} from "./common.tsx";
+import {ReadoutConfiguration} from "./common.tsx";
 
 type ReadoutValue = {
     current: number,
@@ -13,41 +13,36 @@ const Widget: React.FC<{ project: Project, behavior: WidgetBehavior<"readout"> }
                                                                                          project,
                                                                                          behavior
                                                                                      }) => {
-    const rawBuffer = useRef<string>("")
     const values = useRef<Map<string, ReadoutValue>>(new Map())
 
     const [displayValues, setDisplayValues] = useState(() => new Map<string, ReadoutValue>());
 
-    const handleReceive = useCallback((buf: Uint8Array) => {
-        const decoded = new TextDecoder().decode(buf)
+    const handleReadout = useCallback((component: string, value: number) => {
+        if (!behavior.components.includes(component)) return
 
-        const full = rawBuffer.current + decoded;
+        // Get the previous data for this component
+        const past = values.current.get(component);
 
-        const result = parseKnownReadouts(
-            full,
-            behavior.components
-        );
-
-        for (const readout of result.values) {
-            const {component, value} = readout;
-
-            // Get the previous data for this component
-            const past = values.current.get(component);
-
-            // Set the new data, accumulating count and average
-            values.current.set(component, {
-                current: value,
-                average: past ? (past.average * past.count + value) / (past.count + 1) : value,
-                count: past ? past.count + 1 : 1
-            });
-        }
-
-        rawBuffer.current = full.substring(result.lastMatchEnd);
+        // Set the new data, accumulating count and average
+        values.current.set(component, {
+            current: value,
+            average: past ? (past.average * past.count + value) / (past.count + 1) : value,
+            count: past ? past.count + 1 : 1
+        });
     }, [behavior]);
 
     useEffect(() => {
-        const raw = project.registerListener.raw((buf) => {
-            handleReceive(buf);
+        // Start from the latest values read before the widget was mounted
+        for (const component of behavior.components) {
+            project.telemetryLatest(component).then((sample) => {
+                if (sample && !values.current.has(component)) {
+                    values.current.set(component, {current: sample.value, average: sample.value, count: 1})
+                }
+            })
+        }
+
+        const readout = project.registerListener.readout((component, value) => {
+            handleReadout(component, value);
         })
 
         let animationHandle: number;
@@ -60,7 +55,7 @@ const Widget: React.FC<{ project: Project, behavior: WidgetBehavior<"readout"> }
         animationHandle = requestAnimationFrame(onAnimate);
 
         return () => {
-            project.unregisterListener.raw(raw)
+            project.unregisterListener.readout(readout)
             cancelAnimationFrame(animationHandle)
         }
     }, [])
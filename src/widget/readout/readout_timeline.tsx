@@ -3,9 +3,9 @@ import {WidgetBehavior, WidgetHandler, ToolContainerProps} from "../widget.ts";
 import React, {useCallback, useEffect, useRef, useState} from 'react';
 import {CartesianGrid, Label, Line, LineChart, ResponsiveContainer, Tooltip, XAxis, YAxis} from 'recharts';
 import {Project} from "../../device.tsx";
-import {READOUT_REGEX, ReadoutConfiguration} from "./common.tsx";
+import {ReadoutConfiguration} from "./common.tsx";
 
-type TimeSpan = 100 | 1000 | 5000 | 10000 | 20000 | 30000 | 60000;
+type TimeSpan = 10 | 100 | 1000 | 10000 | 60000 | 600000 | 3600000;
 
 const LINE_COLORS = [
     "#2196F3", // Blue
@@ -30,115 +30,98 @@ const LINE_COLORS = [
     "#C2185B"  // Deep Pink
 ]
 
-const GROWTH_CONSTANT = 30
+// How often the plot asks the backend for the window, in ms
+const REFRESH_INTERVAL = 250
 
-const DO_SCROLL = false
+function formatSpan(span: TimeSpan): string {
+    if (span >= 3600000) return `${span / 3600000}h`
+    if (span >= 60000) return `${span / 60000}min`
+    if (span >= 1000) return `${span / 1000}s`
+
+    return `${span}ms`
+}
 
 const Widget: React.FC<{ project: Project, behavior: WidgetBehavior<"readout"> }> = ({
                                                                                          project,
                                                                                          behavior
                                                                                      }) => {
-    const [_, setRaw] = useState<string>("")
-    const [data, setData] = useState<{ time: number, component: string, value: number }[]>([]);
-    // Component -> averaged components
+    // Monotonic time of the newest readout in microseconds, the window ends there
+    const latest = useRef<number | null>(null)
+    // One row per plotted sample, keyed by component
     const [chartData, setChartData] = useState<({ time: number } & any)[]>([]);
     const [chartWidth, setChartWidth] = useState<number>(0);
 
     const [zoomLevel, setZoomLevel] = useState<number>(3);
-    const timeSpans: TimeSpan[] = [100, 1000, 5000, 10000, 20000, 30000, 60000];
+    const timeSpans: TimeSpan[] = [10, 100, 1000, 10000, 60000, 600000, 3600000];
     const timeSpan = timeSpans[zoomLevel];
 
     const containerRef = useRef<HTMLDivElement>(null);
 
     // A helper function to format timestamps for the chart tooltip.
     const formatXAxis = (tickItem: number) => {
-        return new Date(tickItem).toLocaleTimeString();
-    };
-
-    const handleReceive = useCallback((buf: Uint8Array) => {
-        const decoded = new TextDecoder().decode(buf)
+        const date = new Date(tickItem)
 
-        setRaw((raw) => {
-            let received = []
-            const full = raw + decoded
+        // Milliseconds matter once the window is a few seconds long
+        if (timeSpan < 10000) {
+            return `${date.toLocaleTimeString()}.${String(date.getMilliseconds()).padStart(3, "0")}`
+        }
 
-            let lastIndex = 0
-            let match;
+        return date.toLocaleTimeString();
+    };
 
-            while (match = READOUT_REGEX.exec(full)) {
-                lastIndex = match.index + match[0].length
-                const component = match[1]
-                const value = match[2]
+    const minWidth = () => {
+        // 25 is relatively the padding of the bounding containers
+        return (containerRef.current?.getBoundingClientRect()?.width ?? 25) - 25
+    }
 
-                if (behavior.components.includes(component)) {
-                    received.push({
-                        time: Date.now(),
-                        component: component,
-                        value: Number.parseFloat(value)
-                    })
+    useEffect(() => {
+        // Show what was read before the widget was mounted
+        Promise.all(behavior.components.map((component) => project.telemetryLatest(component))).then((samples) => {
+            for (const sample of samples) {
+                if (sample && (latest.current === null || sample.time.monotonic > latest.current)) {
+                    latest.current = sample.time.monotonic
                 }
             }
-
-            setData((curr) => [
-                ...curr,
-                ...received,
-            ])
-
-            return full.substring(lastIndex, full.length)
         })
-    }, [behavior]);
 
-    useEffect(() => {
-        const raw = project.registerListener.raw((buf) => {
-            handleReceive(buf);
+        const readout = project.registerListener.readout((component, _, time) => {
+            if (behavior.components.includes(component)) {
+                latest.current = time.monotonic
+            }
         })
 
-        return () => project.unregisterListener.raw(raw)
-    }, [])
+        return () => project.unregisterListener.readout(readout)
+    }, [project, behavior])
 
-    const minWidth = () => {
-        // 25 is relatively the padding of the bounding containers
-        return (containerRef.current?.getBoundingClientRect()?.width ?? 25) - 25
-    }
+    // The backend keeps the history and thins it to about a sample per pixel,
+    // so an hour of 1 kHz data plots as fast as a second of it
+    const refresh = useCallback(async () => {
+        const to = latest.current
+        if (to === null) return
 
-    useEffect(() => {
-        // <Component name -> <Time (in ms since 1970), values[]>>
-        const sortedValues = new Map<number, Map<string, number[]>>()
+        const width = Math.max(1, Math.floor(minWidth()))
+        const from = Math.max(0, to - timeSpan * 1000)
 
-        data.forEach(({time, component, value}) => {
-            const timeSlot = Math.floor(time / timeSpan) * timeSpan;
-            if (!sortedValues.has(timeSlot)) {
-                sortedValues.set(timeSlot, new Map());
-            }
-            const componentEntry = sortedValues.get(timeSlot)!;
-            if (!componentEntry.has(component)) {
-                componentEntry.set(component, [])
-            }
-            componentEntry.get(component)!.push(value)
-        });
+        const series = await Promise.all(behavior.components.map((component) =>
+            project.telemetryDownsample(component, width, "lttb", from, to)
+        ))
 
-        // let chartData: ({ time: number } & any)[] = []
+        const rows = series
+            .flatMap((it, index) => it.type === "Lttb" ? it.data.map((sample) => ({
+                time: sample.time.unix / 1000,
+                [behavior.components[index]]: sample.value
+            })) : [])
+            .sort((a, b) => a.time - b.time)
 
-        const chartData = Array.from(sortedValues.entries())
-            .sort(([a], [b]) => a - b)
-            .flatMap(([time, map]) => {
-                const base: { time: number } & any = {
-                    time: time
-                }
-                Array.from(map.entries()).forEach(([component, values]) => {
-                    base[component] = values.reduce((a, b) => a + b) / values.length;
-                })
-                return base
-            })
+        setChartData(rows)
+    }, [project, behavior, timeSpan])
 
-        setChartData(chartData);
+    useEffect(() => {
+        refresh()
+        const handle = setInterval(refresh, REFRESH_INTERVAL)
 
-        if (DO_SCROLL && sortedValues.size * GROWTH_CONSTANT > minWidth()) {
-            setChartWidth(sortedValues.size * GROWTH_CONSTANT)
-        } else {
-            setChartWidth(minWidth())
-        }
-    }, [zoomLevel, data])
+        return () => clearInterval(handle)
+    }, [refresh])
 
     useEffect(() => {
         setChartWidth(minWidth())
@@ -161,7 +144,7 @@ const Widget: React.FC<{ project: Project, behavior: WidgetBehavior<"readout"> }
                     <div className="flex justify-between items-start mb-4">
                     </div>
                     <div className="absolute top-2 right-2 flex space-x-2 z-10 align-middle">
-                        <h1 className={"my-auto"}>{timeSpans[zoomLevel]}ms</h1>
+                        <h1 className={"my-auto"}>{formatSpan(timeSpan)}</h1>
                         <button
                             onClick={handleZoomIn}
                             disabled={zoomLevel === 0}
@@ -195,7 +178,8 @@ const Widget: React.FC<{ project: Project, behavior: WidgetBehavior<"readout"> }
                             >
                                 <LineChart data={chartData}>
                                     <CartesianGrid strokeDasharray="3 3" stroke="#E0E0E0"/>
-                                    <XAxis dataKey="time" tickFormatter={formatXAxis} stroke="#808080">
+                                    <XAxis dataKey="time" type="number" domain={["dataMin", "dataMax"]}
+                                           tickFormatter={formatXAxis} stroke="#808080">
                                         <Label value="Time" offset={-5} position="insideBottom"
                                                style={{fill: '#808080'}}/>
                                     </XAxis>
@@ -208,14 +192,11 @@ const Widget: React.FC<{ project: Project, behavior: WidgetBehavior<"readout"> }
                                             Number(value).toFixed(3),
                                             name === "average" ? "Average Value" : name,
                                         ]}
-                                        labelFormatter={(label) => {
-                                            const date = new Date(label);
-                                            return date.toLocaleTimeString();
-                                        }}
+                                        labelFormatter={(label) => formatXAxis(label)}
                                     />
                                     {behavior.components.map((component, index) => (
                                         <Line key={index} type="monotone" dataKey={component}
-                                              name={`Average ${component}`}
+                                              name={component} connectNulls
                                               stroke={LINE_COLORS[index % LINE_COLORS.length]}
                                               strokeWidth={2}
                                               dot={false} isAnimationActive={false}/>